@@ -0,0 +1,37 @@
+#![feature(test)]
+
+extern crate test;
+
+use ip_subnetting::{
+    net::{self, NetV4},
+    task::{TaskGen, TaskV4},
+};
+use test::{black_box, Bencher};
+
+// The subnets of a /8 split into 65536 /24s, the mask of each one is
+// computed once per subnet like when writing out the solution.
+fn split() -> Vec<NetV4> {
+    TaskV4::parse("10.0.0.0/8 -> 65536")
+        .unwrap()
+        .target_networks()
+}
+
+#[bench]
+fn split_8_into_65536_table(b: &mut Bencher) {
+    let nets = split();
+    b.iter(|| {
+        nets.iter()
+            .map(|net| net::sn_from_cidr_u32(black_box(net.cidr())))
+            .fold(0u32, |acc, sn| acc ^ sn)
+    });
+}
+
+#[bench]
+fn split_8_into_65536_shift(b: &mut Bencher) {
+    let nets = split();
+    b.iter(|| {
+        nets.iter()
+            .map(|net| net::sn_from_cidr_u32_shift(black_box(net.cidr())))
+            .fold(0u32, |acc, sn| acc ^ sn)
+    });
+}
@@ -0,0 +1,76 @@
+#![feature(test)]
+
+extern crate test;
+
+use ip_subnetting::task::{TaskGen, TaskV4};
+use std::net::Ipv4Addr;
+use test::{black_box, Bencher};
+
+const TASK: &str = "10.0.0.0/16 -> 4096";
+
+// Copy of the code before the subnet mask was cached in GenNet: the target
+// CIDR and both masks are recomputed for every subnet.
+fn old_mask(cidr: u8) -> u32 {
+    u32::MAX.checked_shl(32 - cidr as u32).unwrap_or(0)
+}
+
+fn old_calc_subnet_address(sna: u32, sn: u32, target_cidr: u8, net_idx: u32) -> Ipv4Addr {
+    let mask = !sn & old_mask(target_cidr);
+
+    let num: u32 = (net_idx << (u32::BITS - target_cidr as u32)) & mask;
+    Ipv4Addr::from(num | sna)
+}
+
+#[allow(dead_code)]
+struct OldNet {
+    initial_ip: Ipv4Addr,
+    na: Ipv4Addr,
+    bc: Ipv4Addr,
+    host_from: Ipv4Addr,
+    host_until: Ipv4Addr,
+    cidr: u8,
+}
+
+impl OldNet {
+    fn new(ip: Ipv4Addr, cidr: u8) -> Self {
+        let na = u32::from(ip) & old_mask(cidr);
+        let bc = na | !old_mask(cidr);
+        OldNet {
+            initial_ip: ip,
+            na: Ipv4Addr::from(na),
+            bc: Ipv4Addr::from(bc),
+            host_from: Ipv4Addr::from(na + 1),
+            host_until: Ipv4Addr::from(bc - 1),
+            cidr,
+        }
+    }
+
+    fn subnetmask_bits(&self) -> u32 {
+        old_mask(self.cidr)
+    }
+}
+
+#[bench]
+fn split_16_into_4096_recomputed(b: &mut Bencher) {
+    let network = OldNet::new(Ipv4Addr::new(10, 0, 0, 0), 16);
+    let subnets: u32 = 4096;
+    let target_cidr = || subnets.next_power_of_two().ilog2() as u8 + network.cidr;
+
+    b.iter(|| {
+        let tcidr = target_cidr();
+        let sna = u32::from(network.na);
+        let mut nas = vec![];
+        for i in 0..2u32.pow((tcidr - network.cidr).into()) {
+            let na = old_calc_subnet_address(sna, network.subnetmask_bits(), target_cidr(), i);
+            nas.push(OldNet::new(na, tcidr));
+        }
+        black_box(nas)
+    });
+}
+
+#[bench]
+fn split_16_into_4096_hoisted(b: &mut Bencher) {
+    let task = TaskV4::parse(TASK).unwrap();
+
+    b.iter(|| black_box(task.target_networks()));
+}
@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 
 #[derive(clap::Parser, Debug)]
 pub enum IpMode {
@@ -19,9 +21,31 @@ pub enum Command {
         #[arg(long="mic", default_value_t = 16)]
         min_cidr: u8,
         #[arg(long="mac", default_value_t = 28)]
-        max_cidr: u8
+        max_cidr: u8,
+        /// Also write a Markdown worksheet with all tasks followed by all solutions
+        #[arg(long)]
+        combined: Option<PathBuf>,
     },
     Solve {
         input: String,
+        /// Show network, broadcast, first and last host and the usable host count per subnet
+        #[arg(long)]
+        verbose: bool,
+        /// Always print IPv4-mapped IPv6 addresses as ::ffff:a.b.c.d
+        #[arg(long)]
+        v4_mapped: bool,
+        /// Only print a one-line summary of the split
+        #[arg(long, conflicts_with = "verbose")]
+        oneline: bool,
+        /// Only print the network address and CIDR of each subnet, one per line
+        #[arg(long, conflicts_with_all = ["verbose", "oneline"])]
+        addresses_only: bool,
+    },
+    /// Check a student's answers against the correct solutions
+    Grade {
+        /// Task list as printed by gen
+        tasks: PathBuf,
+        /// One CSV block per task: Nr,Netzadresse,Broadcastadresse,Erster Host,Letzter Host
+        answers: PathBuf,
     },
 }
@@ -0,0 +1,306 @@
+use regex::Regex;
+use std::fmt::Display;
+
+use crate::{
+    net::{GenNet, IpInfo, ParseNetError},
+    task::{Task, TaskGen},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    NetworkAddress,
+    BroadcastAddress,
+    FirstHost,
+    LastHost,
+}
+
+impl Display for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Field::NetworkAddress => "Netzadresse",
+            Field::BroadcastAddress => "Broadcastadresse",
+            Field::FirstHost => "Erster Host",
+            Field::LastHost => "Letzter Host",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One subnet as written down by a student, fields are kept as given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubnetAnswer {
+    pub network_address: String,
+    pub broadcast_address: String,
+    pub first_host: String,
+    pub last_host: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mistake {
+    WrongField {
+        subnet: usize,
+        field: Field,
+        expected: String,
+        given: String,
+    },
+    /// The row of this subnet doesn't have the five CSV fields.
+    MalformedRow {
+        subnet: usize,
+        row: String,
+    },
+    MissingSubnet {
+        subnet: usize,
+    },
+    ExtraSubnet {
+        subnet: usize,
+    },
+}
+
+impl Display for Mistake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mistake::WrongField {
+                subnet,
+                field,
+                expected,
+                given,
+            } => write!(
+                f,
+                "Netz {} {}: erwartet {}, angegeben {}",
+                subnet, field, expected, given
+            ),
+            Mistake::MalformedRow { subnet, row } => {
+                write!(f, "Netz {} konnte nicht gelesen werden: {}", subnet, row)
+            }
+            Mistake::MissingSubnet { subnet } => write!(f, "Netz {} fehlt", subnet),
+            Mistake::ExtraSubnet { subnet } => write!(f, "Netz {} ist zu viel", subnet),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GradeResult {
+    pub task: usize,
+    pub mistakes: Vec<Mistake>,
+}
+
+impl GradeResult {
+    pub fn passed(&self) -> bool {
+        self.mistakes.is_empty()
+    }
+}
+
+/// Parses a task list as printed by `gen`, one task per line with an
+/// optional `N.:` prefix.
+pub fn parse_tasks<Net: IpInfo>(text: &str) -> Result<Vec<Task<Net>>, ParseNetError>
+where
+    Task<Net>: TaskGen<Net>,
+{
+    let prefix = Regex::new(r"^\d+\.:\s*").unwrap();
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| Task::<Net>::parse(&prefix.replace(line, "")))
+        .collect()
+}
+
+/// Parses an answer file: one block of CSV rows per task (as written by
+/// `Task::to_csv`), blocks separated by blank lines. Rows that don't start
+/// with a number are headers and skipped. Numbered rows without all five
+/// fields are kept as `Err` with the row text, so later rows keep their place.
+pub fn parse_answers(text: &str) -> Vec<Vec<Result<SubnetAnswer, String>>> {
+    let mut answers = vec![];
+    let mut block = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() {
+            if !block.is_empty() {
+                answers.push(block);
+                block = vec![];
+            }
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        if fields[0].parse::<usize>().is_err() {
+            continue;
+        }
+        if fields.len() != 5 {
+            block.push(Err(line.to_owned()));
+            continue;
+        }
+        block.push(Ok(SubnetAnswer {
+            network_address: fields[1].to_owned(),
+            broadcast_address: fields[2].to_owned(),
+            first_host: fields[3].to_owned(),
+            last_host: fields[4].to_owned(),
+        }));
+    }
+    if !block.is_empty() {
+        answers.push(block);
+    }
+    answers
+}
+
+fn check_field<Net: IpInfo>(
+    mistakes: &mut Vec<Mistake>,
+    subnet: usize,
+    field: Field,
+    expected: Net::IpType,
+    given: &str,
+) {
+    if given.parse::<Net::IpType>().ok() != Some(expected) {
+        mistakes.push(Mistake::WrongField {
+            subnet,
+            field,
+            expected: expected.to_string(),
+            given: given.to_owned(),
+        });
+    }
+}
+
+fn grade_subnet<Net: IpInfo>(
+    mistakes: &mut Vec<Mistake>,
+    subnet: usize,
+    network: &GenNet<Net>,
+    answer: &SubnetAnswer,
+) {
+    let (hfrom, hto) = network.host();
+    check_field::<Net>(
+        mistakes,
+        subnet,
+        Field::NetworkAddress,
+        network.network_address(),
+        &answer.network_address,
+    );
+    check_field::<Net>(
+        mistakes,
+        subnet,
+        Field::BroadcastAddress,
+        network.broadcast_address(),
+        &answer.broadcast_address,
+    );
+    check_field::<Net>(
+        mistakes,
+        subnet,
+        Field::FirstHost,
+        hfrom,
+        &answer.first_host,
+    );
+    check_field::<Net>(mistakes, subnet, Field::LastHost, hto, &answer.last_host);
+}
+
+/// Compares the answers with the correct split of each task. Tasks without
+/// an answer block count as if all subnets were missing.
+pub fn grade<Net: IpInfo>(
+    tasks: &[Task<Net>],
+    answers: &[Vec<Result<SubnetAnswer, String>>],
+) -> Vec<GradeResult> {
+    tasks
+        .iter()
+        .enumerate()
+        .map(|(idx, task)| {
+            let answer = answers.get(idx).map(Vec::as_slice).unwrap_or_default();
+            let mut mistakes = vec![];
+            let mut networks = task.target_networks_iter();
+            let mut subnet = 0;
+            loop {
+                subnet += 1;
+                match (networks.next(), answer.get(subnet - 1)) {
+                    (Some(network), Some(Ok(given))) => {
+                        grade_subnet(&mut mistakes, subnet, &network, given)
+                    }
+                    (Some(_), Some(Err(row))) => mistakes.push(Mistake::MalformedRow {
+                        subnet,
+                        row: row.clone(),
+                    }),
+                    (Some(_), None) => mistakes.push(Mistake::MissingSubnet { subnet }),
+                    (None, Some(_)) => mistakes.push(Mistake::ExtraSubnet { subnet }),
+                    (None, None) => break,
+                }
+            }
+            GradeResult {
+                task: idx + 1,
+                mistakes,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::V4;
+
+    #[test]
+    fn malformed_row_keeps_later_rows_in_place() {
+        let tasks = parse_tasks::<V4>("10.0.0.0/24 -> 4").unwrap();
+        let answers = parse_answers(
+            "Nr,Netzadresse,Broadcastadresse,Erster Host,Letzter Host\n\
+             1,10.0.0.0,10.0.0.63,10.0.0.1,10.0.0.62\n\
+             2,10.0.0.64,10.0.0.127,10.0.0.65\n\
+             3,10.0.0.128,10.0.0.191,10.0.0.129,10.0.0.190\n\
+             4,10.0.0.192,10.0.0.255,10.0.0.193,10.0.0.254\n",
+        );
+        assert_eq!(
+            grade(&tasks, &answers)[0].mistakes,
+            [Mistake::MalformedRow {
+                subnet: 2,
+                row: "2,10.0.0.64,10.0.0.127,10.0.0.65".to_owned()
+            }]
+        );
+    }
+
+    const TASKS: &str = "   1.:    10.0.0.0/8  -> 2\n   2.: 192.168.0.0/24 -> 2\n";
+
+    #[test]
+    fn grade_wrong_broadcast() {
+        let tasks = parse_tasks::<V4>(TASKS).unwrap();
+        let answers = parse_answers(
+            "1,10.0.0.0,10.127.255.255,10.0.0.1,10.127.255.254\n\
+             2,10.128.0.0,10.255.255.255,10.128.0.1,10.255.255.254\n\
+             \n\
+             1,192.168.0.0,192.168.0.127,192.168.0.1,192.168.0.126\n\
+             2,192.168.0.128,192.168.0.254,192.168.0.129,192.168.0.254\n",
+        );
+        let results = grade(&tasks, &answers);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].passed());
+        assert!(!results[1].passed());
+        assert!(matches!(
+            results[1].mistakes.as_slice(),
+            [Mistake::WrongField {
+                subnet: 2,
+                field: Field::BroadcastAddress,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn grade_missing_and_extra_subnets() {
+        let tasks = parse_tasks::<V4>(TASKS).unwrap();
+        let answers = parse_answers(
+            "1,10.0.0.0,10.127.255.255,10.0.0.1,10.127.255.254\n\
+             \n\
+             1,192.168.0.0,192.168.0.127,192.168.0.1,192.168.0.126\n\
+             2,192.168.0.128,192.168.0.255,192.168.0.129,192.168.0.254\n\
+             3,192.168.1.0,192.168.1.127,192.168.1.1,192.168.1.126\n",
+        );
+        let results = grade(&tasks, &answers);
+        assert_eq!(results[0].mistakes, [Mistake::MissingSubnet { subnet: 2 }]);
+        assert_eq!(results[1].mistakes, [Mistake::ExtraSubnet { subnet: 3 }]);
+    }
+
+    #[test]
+    fn grade_without_answers() {
+        let tasks = parse_tasks::<V4>(TASKS).unwrap();
+        let results = grade(&tasks, &[]);
+        assert_eq!(
+            results[1].mistakes,
+            [
+                Mistake::MissingSubnet { subnet: 1 },
+                Mistake::MissingSubnet { subnet: 2 }
+            ]
+        );
+    }
+}
@@ -1,45 +1,103 @@
-#![feature(step_trait)]
-
 mod cli;
-mod net;
-mod task;
 
 use clap::Parser;
-use task::{TaskGen, TaskV4};
-
-use crate::task::TaskV6;
+use colored::Colorize;
+use ip_subnetting::{
+    grade,
+    net::{IpInfo, V4, V6},
+    task::{combined_markdown, format_ip, Task, TaskColumns, TaskGen},
+};
+use std::{
+    fmt::Display,
+    fs,
+    ops::{BitAnd, Not},
+    path::Path,
+    process,
+};
 
-fn handle_v4(cmd: cli::Command) {
+fn handle<Net: IpInfo>(cmd: cli::Command)
+where
+    Task<Net>: TaskGen<Net>,
+    <<Net as IpInfo>::Bits as Not>::Output: BitAnd<<Net as IpInfo>::Bits>,
+{
     match cmd {
-        cli::Command::Gen { count, max_subnets, min_subnets, min_cidr, max_cidr } => {
-            for i in 1..=count {
-                TaskV4::rand(min_subnets, max_subnets, min_cidr, max_cidr).print_task_cli(Some(i));
+        cli::Command::Gen { count, max_subnets, min_subnets, min_cidr, max_cidr, combined } => {
+            let tasks: Vec<Task<Net>> = (0..count)
+                .map(|_| Task::<Net>::rand(min_subnets, max_subnets, min_cidr, max_cidr))
+                .collect::<Result<_, _>>()
+                .unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                });
+            let cols = TaskColumns::fit(&tasks);
+            for (i, task) in tasks.iter().enumerate() {
+                task.print_task_cli(Some(i as u32 + 1), cols);
+            }
+            if let Some(path) = combined {
+                fs::write(&path, combined_markdown(&tasks)).unwrap_or_else(|e| {
+                    eprintln!("Could not write {}: {}", path.display(), e);
+                    process::exit(1);
+                });
             }
         }
-        cli::Command::Solve { input } => {
-            TaskV4::parse(&input).print_cli();
-        }
-    }
-}
-
-fn handle_v6(cmd: cli::Command) {
-    match cmd {
-        cli::Command::Gen { count, max_subnets, min_subnets, min_cidr, max_cidr } => {
-            for i in 1..=count {
-                TaskV6::rand(min_subnets, max_subnets, min_cidr, max_cidr).print_task_cli(Some(i));
+        cli::Command::Solve { input, verbose, v4_mapped, oneline, addresses_only } => {
+            let task = Task::<Net>::parse(&input).unwrap_or_else(|e| usage_error(e, SOLVE_USAGE));
+            if addresses_only {
+                for network in task.target_networks_iter() {
+                    println!("{}/{}", format_ip::<Net>(network.network_address(), v4_mapped), network.cidr());
+                }
+                return;
+            }
+            if oneline {
+                println!("{}", task.summary_line(v4_mapped));
+                return;
+            }
+            task.print_cli(v4_mapped);
+            if verbose {
+                println!("{}", task.subnets_markdown_table_verbose(v4_mapped));
             }
         }
-        cli::Command::Solve { input } => {
-            TaskV6::parse(&input).print_cli();
+        cli::Command::Grade { tasks, answers } => {
+            let tasks = grade::parse_tasks::<Net>(&read(&tasks)).unwrap_or_else(|e| usage_error(e, GRADE_USAGE));
+            let answers = grade::parse_answers(&read(&answers));
+            let results = grade::grade(&tasks, &answers);
+            for result in results.iter() {
+                if result.passed() {
+                    println!("Aufgabe {}: {}", result.task, "bestanden".green());
+                } else {
+                    println!("Aufgabe {}: {}", result.task, "nicht bestanden".red());
+                    for mistake in result.mistakes.iter() {
+                        println!("  {}", mistake);
+                    }
+                }
+            }
+            if !results.iter().all(|r| r.passed()) {
+                process::exit(1);
+            }
         }
     }
 }
 
+const SOLVE_USAGE: &str = "solve \"NET/CIDR -> N\"";
+const GRADE_USAGE: &str = "grade <TASKS> <ANSWERS>";
+
+fn usage_error(e: impl Display, usage: &str) -> ! {
+    eprintln!("{} {}", "error:".red(), e);
+    eprintln!();
+    eprintln!("Usage: ipaufgabengenerator <v4|v6> {}", usage);
+    process::exit(2);
+}
+
+fn read(path: &Path) -> String {
+    fs::read_to_string(path)
+        .unwrap_or_else(|e| usage_error(format!("Could not read {}: {}", path.display(), e), GRADE_USAGE))
+}
+
 fn main() {
     let args = cli::IpMode::parse();
 
     match args {
-        cli::IpMode::V4(cmd) => handle_v4(cmd),
-        cli::IpMode::V6(cmd) => handle_v6(cmd),
+        cli::IpMode::V4(cmd) => handle::<V4>(cmd),
+        cli::IpMode::V6(cmd) => handle::<V6>(cmd),
     }
 }
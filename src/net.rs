@@ -1,19 +1,25 @@
 use regex::Regex;
 use std::{
-    fmt::{Debug, Display},
+    cmp::min,
+    error::Error,
+    fmt::{Binary, Debug, Display, UpperHex},
+    hash::{Hash, Hasher},
     iter::Step,
-    mem::size_of,
-    net::{Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     ops::{BitAnd, BitOr, Not},
+    str::FromStr,
 };
 
-pub trait IpByteTypeHelper {
+pub trait IpByteTypeHelper: Sized {
     const MAX: Self;
     const BITS: u8;
     const ONE: Self;
     const ZERO: Self;
 
     fn pow(base: u32, pow: u32) -> Self;
+    fn checked_pow(base: u32, pow: u32) -> Option<Self>;
+    fn trailing_zeros(self) -> u32;
+    fn mask(cidr: u8) -> Self;
 }
 
 impl IpByteTypeHelper for u32 {
@@ -25,6 +31,18 @@ impl IpByteTypeHelper for u32 {
     fn pow(base: u32, pow: u32) -> Self {
         base.pow(pow)
     }
+
+    fn checked_pow(base: u32, pow: u32) -> Option<Self> {
+        base.checked_pow(pow)
+    }
+
+    fn trailing_zeros(self) -> u32 {
+        u32::trailing_zeros(self)
+    }
+
+    fn mask(cidr: u8) -> Self {
+        sn_from_cidr_u32(cidr)
+    }
 }
 
 impl IpByteTypeHelper for u128 {
@@ -34,7 +52,23 @@ impl IpByteTypeHelper for u128 {
     const ZERO: u128 = 0u128;
 
     fn pow(base: u32, pow: u32) -> Self {
-        base.pow(pow).into()
+        u128::from(base).pow(pow)
+    }
+
+    fn checked_pow(base: u32, pow: u32) -> Option<Self> {
+        u128::from(base).checked_pow(pow)
+    }
+
+    fn trailing_zeros(self) -> u32 {
+        u128::trailing_zeros(self)
+    }
+
+    fn mask(cidr: u8) -> Self {
+        if cidr == 0 {
+            0
+        } else {
+            u128::MAX << (u128::BITS - cidr as u32)
+        }
     }
 }
 
@@ -64,6 +98,10 @@ impl<T> IpByteType for T where
 pub trait IpTrait<Bits: IpByteType> {
     fn from_proxy(bits: Bits) -> Self;
     fn bits(&self) -> Bits;
+
+    fn to_ipv4_mapped(&self) -> Option<Ipv4Addr> {
+        None
+    }
 }
 
 impl IpTrait<u32> for Ipv4Addr {
@@ -84,20 +122,38 @@ impl IpTrait<u128> for Ipv6Addr {
     fn bits(&self) -> u128 {
         self.to_owned().into()
     }
+
+    fn to_ipv4_mapped(&self) -> Option<Ipv4Addr> {
+        let bits = self.bits();
+        if bits >> 32 == 0xffff {
+            Some(Ipv4Addr::from(bits as u32))
+        } else {
+            None
+        }
+    }
 }
 
 pub trait IpInfo {
     type Bits: IpByteType
+        + From<u32>
+        + Into<u128>
         + From<<Self::Bits as BitAnd>::Output>
         + From<<Self::Bits as BitOr>::Output>
         + From<<Self::Bits as Not>::Output>
         + Copy
-        + Clone;
-    type IpType: IpTrait<Self::Bits> + Copy + Clone + Debug + Display;
+        + Clone
+        + Ord
+        + Hash
+        + Display
+        + UpperHex
+        + Binary;
+    type IpType: IpTrait<Self::Bits> + Copy + Clone + Debug + Display + FromStr + PartialEq;
 
-    fn calc_subnet_address(
+    /// `mask` selects the subnet bits between the base and the target prefix,
+    /// see [`subnet_index_mask`]. It is not the subnet mask of the base network.
+    fn calc_masked_subnet_address(
         sna: Self::Bits,
-        sn: Self::Bits,
+        mask: Self::Bits,
         target_cidr: u8,
         net_idx: Self::Bits,
     ) -> Self::IpType;
@@ -109,10 +165,12 @@ impl IpInfo for V4 {
     type IpType = Ipv4Addr;
     type Bits = u32;
 
-    fn calc_subnet_address(sna: u32, sn: u32, target_cidr: u8, net_idx: u32) -> Ipv4Addr {
-        let mask = !sn & sn_from_cidr_gen_bits::<Self>(target_cidr);
-
-        let num: u32 = (net_idx << (u32::BITS - target_cidr as u32)) & mask;
+    fn calc_masked_subnet_address(sna: u32, mask: u32, target_cidr: u8, net_idx: u32) -> Ipv4Addr {
+        // A /0 target has no subnet bits, the shift would be the full width
+        let num: u32 = net_idx
+            .checked_shl(u32::BITS - target_cidr as u32)
+            .unwrap_or(0)
+            & mask;
         Ipv4Addr::from(num | sna)
     }
 }
@@ -123,21 +181,38 @@ impl IpInfo for V6 {
     type IpType = Ipv6Addr;
     type Bits = u128;
 
-    fn calc_subnet_address(sna: u128, sn: u128, target_cidr: u8, net_idx: u128) -> Ipv6Addr {
-        let mask = !sn & sn_from_cidr_gen_bits::<Self>(target_cidr);
-
-        let num: u128 = (net_idx << (u128::BITS - target_cidr as u32)) & mask;
+    fn calc_masked_subnet_address(
+        sna: u128,
+        mask: u128,
+        target_cidr: u8,
+        net_idx: u128,
+    ) -> Ipv6Addr {
+        let num: u128 = net_idx
+            .checked_shl(u128::BITS - target_cidr as u32)
+            .unwrap_or(0)
+            & mask;
         Ipv6Addr::from(num | sna)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Radix {
+    Dec,
+    Hex,
+    Bin,
+}
+
 pub enum IpType {
     V4,
     V6,
 }
 
 pub fn sn_from_cidr_gen_bits<Ip: IpInfo>(cidr: u8) -> Ip::Bits {
-    Ip::Bits::MAX << (Ip::Bits::BITS - cidr)
+    Ip::Bits::mask(cidr)
+}
+
+pub fn subnet_index_mask<Ip: IpInfo>(sn: Ip::Bits, target_cidr: u8) -> Ip::Bits {
+    (Ip::Bits::from(!sn) & sn_from_cidr_gen_bits::<Ip>(target_cidr)).into()
 }
 
 pub fn sn_from_cidr_gen<Ip: IpInfo>(cidr: u8) -> Ip::IpType {
@@ -155,8 +230,165 @@ pub fn bc_from_ip_and_cidr_gen<Ip: IpInfo>(ip: &Ip::IpType, cidr: u8) -> Ip::IpT
     Ip::IpType::from_proxy((source | mask.into()).into())
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ipv4Class {
+    A,
+    B,
+    C,
+    D,
+    E,
+}
+
+pub fn ipv4_class(ip: Ipv4Addr) -> Ipv4Class {
+    match ip.octets()[0] {
+        0..=127 => Ipv4Class::A,
+        128..=191 => Ipv4Class::B,
+        192..=223 => Ipv4Class::C,
+        224..=239 => Ipv4Class::D,
+        _ => Ipv4Class::E,
+    }
+}
+
+/// Legacy classful prefix length (A: /8, B: /16, C: /24). Class D and E have none.
+pub fn default_class_cidr(class: Ipv4Class) -> Option<u8> {
+    match class {
+        Ipv4Class::A => Some(8),
+        Ipv4Class::B => Some(16),
+        Ipv4Class::C => Some(24),
+        Ipv4Class::D | Ipv4Class::E => None,
+    }
+}
+
+pub trait IpFamily: Sized {
+    type Info: IpInfo<IpType = Self>;
+}
+
+impl IpFamily for Ipv4Addr {
+    type Info = V4;
+}
+
+impl IpFamily for Ipv6Addr {
+    type Info = V6;
+}
+
+/// Network address of `ip` with the prefix `cidr`.
+///
+/// ```
+/// use std::net::{Ipv4Addr, Ipv6Addr};
+///
+/// let v4: Ipv4Addr = "10.1.2.3".parse().unwrap();
+/// assert_eq!(ip_subnetting::network_of(v4, 20), Ipv4Addr::new(10, 1, 0, 0));
+///
+/// let v6: Ipv6Addr = "2001:db8:abcd:12::1".parse().unwrap();
+/// let na: Ipv6Addr = "2001:db8:abcd::".parse().unwrap();
+/// assert_eq!(ip_subnetting::network_of(v6, 48), na);
+/// ```
+pub fn network_of<A: IpFamily>(ip: A, cidr: u8) -> A {
+    na_from_ip_and_cidr_gen::<A::Info>(&ip, cidr)
+}
+
+/// Broadcast address (last address) of `ip` with the prefix `cidr`.
+///
+/// ```
+/// use std::net::{Ipv4Addr, Ipv6Addr};
+///
+/// let v4: Ipv4Addr = "10.1.2.3".parse().unwrap();
+/// assert_eq!(ip_subnetting::broadcast_of(v4, 20), Ipv4Addr::new(10, 1, 15, 255));
+///
+/// let v6: Ipv6Addr = "2001:db8:abcd:12::1".parse().unwrap();
+/// let bc: Ipv6Addr = "2001:db8:abcd:ffff:ffff:ffff:ffff:ffff".parse().unwrap();
+/// assert_eq!(ip_subnetting::broadcast_of(v6, 48), bc);
+/// ```
+pub fn broadcast_of<A: IpFamily>(ip: A, cidr: u8) -> A {
+    bc_from_ip_and_cidr_gen::<A::Info>(&ip, cidr)
+}
+
+fn host_mask<Ip: IpInfo>(host_bits: u8) -> Ip::Bits {
+    if host_bits >= Ip::Bits::BITS {
+        Ip::Bits::MAX
+    } else {
+        (!(Ip::Bits::MAX << host_bits)).into()
+    }
+}
+
+/// Splits the inclusive range `start..=end` into the largest aligned blocks.
+fn aligned_blocks<Ip: IpInfo>(mut start: Ip::Bits, end: Ip::Bits) -> Vec<GenNet<Ip>> {
+    let mut nets = vec![];
+    loop {
+        let mut host_bits = min(start.trailing_zeros(), Ip::Bits::BITS.into()) as u8;
+        while host_bits > 0 && host_mask::<Ip>(host_bits) > end - start {
+            host_bits -= 1;
+        }
+        nets.push(GenNet::new(
+            Ip::IpType::from_proxy(start),
+            Ip::Bits::BITS - host_bits,
+        ));
+
+        let last = start + host_mask::<Ip>(host_bits);
+        if last >= end {
+            break;
+        }
+        start = last + Ip::Bits::ONE;
+    }
+    nets
+}
+
+/// Smallest list of aligned networks that covers the inclusive range
+/// `start..=end`. Empty if `start > end`.
+pub fn range_to_prefixes<Ip: IpInfo>(start: Ip::IpType, end: Ip::IpType) -> Vec<GenNet<Ip>> {
+    if start.bits() > end.bits() {
+        return vec![];
+    }
+    aligned_blocks::<Ip>(start.bits(), end.bits())
+}
+
+/// Smallest list of networks that contains exactly the given addresses.
+pub fn cover_addresses<Ip: IpInfo>(addrs: &[Ip::IpType]) -> Vec<GenNet<Ip>> {
+    let mut bits: Vec<Ip::Bits> = addrs.iter().map(|ip| ip.bits()).collect();
+    bits.sort();
+    bits.dedup();
+
+    let mut nets = vec![];
+    let mut bits = bits.into_iter();
+    let Some(mut start) = bits.next() else {
+        return nets;
+    };
+    let mut end = start;
+    for ip in bits {
+        if ip == end + Ip::Bits::ONE {
+            end = ip;
+        } else {
+            nets.extend(aligned_blocks::<Ip>(start, end));
+            start = ip;
+            end = ip;
+        }
+    }
+    nets.extend(aligned_blocks::<Ip>(start, end));
+    nets
+}
+
+pub const fn sn_from_cidr_u32_shift(cidr: u8) -> u32 {
+    if cidr == 0 {
+        0
+    } else {
+        u32::MAX << (32 - cidr)
+    }
+}
+
+const SN_TABLE_U32: [u32; 33] = {
+    let mut table = [0; 33];
+    let mut cidr = 0;
+    while cidr < table.len() {
+        table[cidr] = sn_from_cidr_u32_shift(cidr as u8);
+        cidr += 1;
+    }
+    table
+};
+
+/// Looks the mask up in a table built from [`sn_from_cidr_u32_shift`] at
+/// compile time.
 pub fn sn_from_cidr_u32(cidr: u8) -> u32 {
-    u32::MAX << (32 - cidr)
+    SN_TABLE_U32[cidr as usize]
 }
 
 pub fn sn_from_cidr(cidr: u8) -> Ipv4Addr {
@@ -169,7 +401,7 @@ pub fn na_from_ip_and_cidr(ip: Ipv4Addr, cidr: u8) -> Ipv4Addr {
 }
 
 pub fn bc_from_ip_and_cidr(ip: Ipv4Addr, cidr: u8) -> Ipv4Addr {
-    let mask = !(u32::MAX << (32 - cidr));
+    let mask = !sn_from_cidr_u32(cidr);
     let source: u32 = ip.into();
     Ipv4Addr::from(source | mask)
 }
@@ -179,39 +411,188 @@ pub struct GenNet<Ip: IpInfo> {
     initial_ip: Ip::IpType,
     na: Ip::IpType,
     bc: Ip::IpType,
+    sn: Ip::IpType,
+    host_bits_set: bool,
     host_from: Ip::IpType,
     host_until: Ip::IpType,
     cidr: u8,
 }
 
-pub trait IpParse {
-    fn parse(text: &str) -> Self;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CidrError {
+    pub cidr: u8,
+    pub max: u8,
+}
+
+impl Display for CidrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CIDR /{} is too big, at most /{} is allowed",
+            self.cidr, self.max
+        )
+    }
+}
+
+impl Error for CidrError {}
+
+impl GenNet<V4> {
+    /// Network and broadcast address, which can't be assigned to hosts. Point
+    /// to point /31 networks (RFC 3021) and single host /32 networks have none.
+    pub fn reserved_addresses(&self) -> Vec<Ipv4Addr> {
+        if self.cidr >= 31 {
+            vec![]
+        } else {
+            vec![self.na, self.bc]
+        }
+    }
+}
+
+impl GenNet<V6> {
+    /// IPv6 has no broadcast, so no address is reserved this way. The host
+    /// range still starts after the network address and ends before the last
+    /// one, like for IPv4.
+    pub fn reserved_addresses(&self) -> Vec<Ipv6Addr> {
+        vec![]
+    }
+}
+
+/// Two networks are equal if they have the same network address and CIDR,
+/// regardless of the address they were created from.
+impl<Ip: IpInfo> PartialEq for GenNet<Ip> {
+    fn eq(&self, other: &Self) -> bool {
+        self.network_address_bits() == other.network_address_bits() && self.cidr == other.cidr
+    }
+}
+
+impl<Ip: IpInfo> Eq for GenNet<Ip> {}
+
+impl<Ip: IpInfo> Hash for GenNet<Ip> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.network_address_bits().hash(state);
+        self.cidr.hash(state);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseNetError {
+    /// An empty network.
+    Empty,
+    /// An empty task.
+    EmptyTask,
+    /// A network that isn't `NET/CIDR`.
+    Malformed(String),
+    /// A task that isn't `NET/CIDR -> N`.
+    MalformedTask(String),
+    NoClassfulDefault(Ipv4Addr),
+    Cidr(CidrError),
+    /// Splitting into `subnets` subnets needs a longer prefix than the address has.
+    TooManySubnets {
+        subnets: u32,
+        target_cidr: u8,
+        max: u8,
+    },
+}
+
+const EXPECTED_NET_FORMAT: &str = r#"expected NET/CIDR, e.g. "10.0.0.0/24""#;
+const EXPECTED_TASK_FORMAT: &str = r#"expected NET/CIDR -> N, e.g. "10.0.0.0/24 -> 4""#;
+
+impl Display for ParseNetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseNetError::Empty => write!(f, "Input is empty, {}", EXPECTED_NET_FORMAT),
+            ParseNetError::EmptyTask => write!(f, "Input is empty, {}", EXPECTED_TASK_FORMAT),
+            ParseNetError::Malformed(input) => {
+                write!(f, "Could not parse \"{}\", {}", input, EXPECTED_NET_FORMAT)
+            }
+            ParseNetError::MalformedTask(input) => {
+                write!(f, "Could not parse \"{}\", {}", input, EXPECTED_TASK_FORMAT)
+            }
+            ParseNetError::NoClassfulDefault(ip) => write!(
+                f,
+                "{} is a class D or E address without a default CIDR, add one like {}/24",
+                ip, ip
+            ),
+            ParseNetError::Cidr(e) => write!(f, "{}", e),
+            ParseNetError::TooManySubnets {
+                subnets,
+                target_cidr,
+                max,
+            } => write!(
+                f,
+                "{} subnets need a /{} prefix, at most /{} is allowed",
+                subnets, target_cidr, max
+            ),
+        }
+    }
+}
+
+impl Error for ParseNetError {}
+
+impl From<CidrError> for ParseNetError {
+    fn from(e: CidrError) -> Self {
+        ParseNetError::Cidr(e)
+    }
+}
+
+/// Fails with [`ParseNetError::Empty`] for empty or whitespace-only input.
+pub fn check_empty(text: &str) -> Result<(), ParseNetError> {
+    if text.trim().is_empty() {
+        Err(ParseNetError::Empty)
+    } else {
+        Ok(())
+    }
+}
+
+pub trait IpParse: Sized {
+    fn parse(text: &str) -> Result<Self, ParseNetError>;
 }
 
 pub type NetV4 = GenNet<V4>;
 pub type NetV6 = GenNet<V6>;
 
+#[derive(Debug)]
+pub enum AnyNet {
+    V4(NetV4),
+    V6(NetV6),
+}
+
 impl<Ip: IpInfo> GenNet<Ip> {
     pub fn new(ip: Ip::IpType, cidr: u8) -> Self {
-        if size_of::<Ip::Bits>() * 8 < cidr as usize {
-            panic!("CIDR to big");
+        Self::try_new(ip, cidr).expect("CIDR to big")
+    }
+
+    pub fn try_new(ip: Ip::IpType, cidr: u8) -> Result<Self, CidrError> {
+        if cidr > Ip::Bits::BITS {
+            return Err(CidrError {
+                cidr,
+                max: Ip::Bits::BITS,
+            });
         }
 
+        let sn = sn_from_cidr_gen::<Ip>(cidr);
         let na = na_from_ip_and_cidr_gen::<Ip>(&ip, cidr);
         let bc = bc_from_ip_and_cidr_gen::<Ip>(&na, cidr);
-        let na_bits = na.bits().clone();
-        let bc_bits = bc.bits().clone();
-        let from = na_bits + Ip::Bits::ONE;
-        let until = bc_bits - Ip::Bits::ONE;
+        let na_bits = na.bits();
+        let bc_bits = bc.bits();
+        // Point to point (/31, /127) and single host (/32, /128) networks use
+        // every address for hosts
+        let (from, until) = if cidr >= Ip::Bits::BITS - 1 {
+            (na_bits, bc_bits)
+        } else {
+            (na_bits + Ip::Bits::ONE, bc_bits - Ip::Bits::ONE)
+        };
 
-        GenNet {
+        Ok(GenNet {
             initial_ip: ip,
             na,
             bc,
+            sn,
+            host_bits_set: ip.bits() != na_bits,
             host_from: Ip::IpType::from_proxy(from),
             host_until: Ip::IpType::from_proxy(until),
             cidr,
-        }
+        })
     }
 
     pub fn network_address(&self) -> Ip::IpType {
@@ -231,17 +612,24 @@ impl<Ip: IpInfo> GenNet<Ip> {
     }
 
     pub fn subnetmask(&self) -> Ip::IpType {
-        sn_from_cidr_gen::<Ip>(self.cidr)
+        self.sn
     }
 
     pub fn subnetmask_bits(&self) -> Ip::Bits {
-        sn_from_cidr_gen_bits::<Ip>(self.cidr)
+        self.sn.bits()
     }
 
     pub fn host(&self) -> (Ip::IpType, Ip::IpType) {
         (self.host_from, self.host_until)
     }
 
+    /// Number of addresses in [`Self::host`]. For IPv4 these are all
+    /// addresses except [`GenNet::<V4>::reserved_addresses`].
+    pub fn usable_hosts(&self) -> Ip::Bits {
+        let (from, until) = self.host_bits();
+        until - from + Ip::Bits::ONE
+    }
+
     pub fn host_bits(&self) -> (Ip::Bits, Ip::Bits) {
         (self.host_from.bits(), self.host_until.bits())
     }
@@ -253,26 +641,437 @@ impl<Ip: IpInfo> GenNet<Ip> {
     pub fn cidr(&self) -> u8 {
         self.cidr
     }
+
+    /// True if the address the network was created from wasn't the network
+    /// address itself, e.g. `192.168.1.37/24`.
+    pub fn has_host_bits(&self) -> bool {
+        self.host_bits_set
+    }
+
+    /// Network address as a single integer, e.g. `3232235776` or `0xC0A80100`
+    /// for `192.168.1.0`. Hex and binary are zero padded to the full width.
+    pub fn network_address_radix(&self, radix: Radix) -> String {
+        let bits = self.network_address_bits();
+        match radix {
+            Radix::Dec => format!("{}", bits),
+            Radix::Hex => format!("0x{:0width$X}", bits, width = Ip::Bits::BITS as usize / 4),
+            Radix::Bin => format!("0b{:0width$b}", bits, width = Ip::Bits::BITS as usize),
+        }
+    }
+
+    /// True if `self` lies completely within `parent`, which includes `self == parent`.
+    pub fn is_subnet_of(&self, parent: &GenNet<Ip>) -> bool {
+        let na: Ip::Bits = (self.network_address_bits() & parent.subnetmask_bits()).into();
+        self.cidr >= parent.cidr && na == parent.network_address_bits()
+    }
+
+    /// Splits the network into subnets of size `new_cidr` and returns the first
+    /// one together with the remaining ones. The remaining ones are built
+    /// eagerly, so this is only meant for splits into a few thousand subnets.
+    /// `None` if `new_cidr` isn't longer than the prefix or the number of
+    /// subnets doesn't fit into `Ip::Bits`, like `/0` into `/32`.
+    pub fn carve(&self, new_cidr: u8) -> Option<(GenNet<Ip>, Vec<GenNet<Ip>>)> {
+        if new_cidr <= self.cidr || new_cidr > Ip::Bits::BITS {
+            return None;
+        }
+
+        let sna = self.network_address_bits();
+        let mask = subnet_index_mask::<Ip>(self.subnetmask_bits(), new_cidr);
+        let children = Ip::Bits::checked_pow(2, (new_cidr - self.cidr).into())?;
+
+        let mut nets = (Ip::Bits::ZERO..children).map(|i| {
+            GenNet::new(
+                Ip::calc_masked_subnet_address(sna, mask, new_cidr, i),
+                new_cidr,
+            )
+        });
+        let first = nets.next()?;
+        Some((first, nets.collect()))
+    }
 }
 
+impl TryFrom<(Ipv4Addr, u8)> for NetV4 {
+    type Error = CidrError;
+
+    fn try_from((ip, cidr): (Ipv4Addr, u8)) -> Result<Self, Self::Error> {
+        NetV4::try_new(ip, cidr)
+    }
+}
+
+impl TryFrom<(Ipv6Addr, u8)> for NetV6 {
+    type Error = CidrError;
+
+    fn try_from((ip, cidr): (Ipv6Addr, u8)) -> Result<Self, Self::Error> {
+        NetV6::try_new(ip, cidr)
+    }
+}
+
+impl TryFrom<(IpAddr, u8)> for AnyNet {
+    type Error = CidrError;
+
+    fn try_from((ip, cidr): (IpAddr, u8)) -> Result<Self, Self::Error> {
+        match ip {
+            IpAddr::V4(ip) => NetV4::try_from((ip, cidr)).map(AnyNet::V4),
+            IpAddr::V6(ip) => NetV6::try_from((ip, cidr)).map(AnyNet::V6),
+        }
+    }
+}
+
+/// Accepts `a.b.c.d/cidr` as well as a bare `a.b.c.d`. Without a prefix the
+/// classful default of the address is assumed (see [`default_class_cidr`]).
+/// This is legacy classful behavior and only exists for IPv4.
 impl IpParse for NetV4 {
-    fn parse(text: &str) -> Self {
-        let re = Regex::new(r"^(?P<ip>\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3})/(?P<cidr>\d{1,2}).?$")
+    fn parse(text: &str) -> Result<Self, ParseNetError> {
+        check_empty(text)?;
+        let malformed = || ParseNetError::Malformed(text.to_owned());
+        let re = Regex::new(r"^(?P<ip>\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3})(/(?P<cidr>\d{1,2}))?$")
             .unwrap();
-        let caps = re.captures(text).unwrap();
-        let ip = caps.name("ip").unwrap();
-        let cidr = caps.name("cidr").unwrap();
+        let caps = re.captures(text).ok_or_else(malformed)?;
+        let ip: Ipv4Addr = caps
+            .name("ip")
+            .unwrap()
+            .as_str()
+            .parse()
+            .map_err(|_| malformed())?;
+        let cidr = match caps.name("cidr") {
+            Some(cidr) => cidr.as_str().parse().map_err(|_| malformed())?,
+            None => {
+                default_class_cidr(ipv4_class(ip)).ok_or(ParseNetError::NoClassfulDefault(ip))?
+            }
+        };
 
-        NetV4::new(ip.as_str().parse().unwrap(), cidr.as_str().parse().unwrap())
+        Ok(NetV4::try_new(ip, cidr)?)
     }
 }
 
 impl IpParse for NetV6 {
-    fn parse(text: &str) -> Self {
-        let re = Regex::new(r"^(?P<ip>([0-9a-fA-F]{1,4}:){7,7}[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,7}:|([0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,5}(:[0-9a-fA-F]{1,4}){1,2}|([0-9a-fA-F]{1,4}:){1,4}(:[0-9a-fA-F]{1,4}){1,3}|([0-9a-fA-F]{1,4}:){1,3}(:[0-9a-fA-F]{1,4}){1,4}|([0-9a-fA-F]{1,4}:){1,2}(:[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:((:[0-9a-fA-F]{1,4}){1,6})|:((:[0-9a-fA-F]{1,4}){1,7}|:)|fe80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}|::(ffff(:0{1,4}){0,1}:){0,1}((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])|([0-9a-fA-F]{1,4}:){1,4}:((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9]))/(?P<cidr>\d{1,2}).?$").unwrap();
-        let caps = re.captures(text).unwrap();
+    fn parse(text: &str) -> Result<Self, ParseNetError> {
+        check_empty(text)?;
+        let malformed = || ParseNetError::Malformed(text.to_owned());
+        let re = Regex::new(r"^(?P<ip>([0-9a-fA-F]{1,4}:){7,7}[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,7}:|([0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,5}(:[0-9a-fA-F]{1,4}){1,2}|([0-9a-fA-F]{1,4}:){1,4}(:[0-9a-fA-F]{1,4}){1,3}|([0-9a-fA-F]{1,4}:){1,3}(:[0-9a-fA-F]{1,4}){1,4}|([0-9a-fA-F]{1,4}:){1,2}(:[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:((:[0-9a-fA-F]{1,4}){1,6})|:((:[0-9a-fA-F]{1,4}){1,7}|:)|fe80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}|::(ffff(:0{1,4}){0,1}:){0,1}((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])|([0-9a-fA-F]{1,4}:){1,4}:((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9]))/(?P<cidr>\d{1,3})$").unwrap();
+        let caps = re.captures(text).ok_or_else(malformed)?;
         let ip = caps.name("ip").unwrap();
         let cidr = caps.name("cidr").unwrap();
-        NetV6::new(ip.as_str().parse().unwrap(), cidr.as_str().parse().unwrap())
+        Ok(NetV6::try_new(
+            ip.as_str().parse().map_err(|_| malformed())?,
+            cidr.as_str().parse().map_err(|_| malformed())?,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4(text: &str) -> Ipv4Addr {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_v4_classful_default() {
+        assert_eq!(NetV4::parse("10.1.2.3").unwrap().cidr(), 8);
+        assert_eq!(NetV4::parse("172.16.0.0").unwrap().cidr(), 16);
+        assert_eq!(NetV4::parse("192.168.1.0").unwrap().cidr(), 24);
+    }
+
+    #[test]
+    fn parse_v4_explicit_prefix() {
+        let net = NetV4::parse("10.0.0.0/20").unwrap();
+        assert_eq!(net.cidr(), 20);
+        assert_eq!(net.network_address(), v4("10.0.0.0"));
+    }
+
+    #[test]
+    fn parse_v4_rejects_trailing_garbage() {
+        for text in ["10.0.0.0/", "10.0.0.0x", "10.0.0.0/24x"] {
+            assert_eq!(
+                NetV4::parse(text).unwrap_err(),
+                ParseNetError::Malformed(text.to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn parse_v4_class_d_needs_prefix() {
+        assert_eq!(
+            NetV4::parse("224.0.0.1").unwrap_err(),
+            ParseNetError::NoClassfulDefault(v4("224.0.0.1"))
+        );
+    }
+
+    #[test]
+    fn parse_v6_rejects_trailing_garbage() {
+        let text = "2001:db8::/64x";
+        assert_eq!(
+            NetV6::parse(text).unwrap_err(),
+            ParseNetError::Malformed(text.to_owned())
+        );
+    }
+
+    #[test]
+    fn try_from_tuple() {
+        let net = NetV4::try_from((v4("10.1.2.3"), 16)).unwrap();
+        assert_eq!(net.network_address(), v4("10.1.0.0"));
+        assert_eq!(net.cidr(), 16);
+    }
+
+    #[test]
+    fn try_from_tuple_cidr_too_big() {
+        assert_eq!(
+            NetV4::try_from((v4("10.0.0.0"), 33)).unwrap_err(),
+            CidrError { cidr: 33, max: 32 }
+        );
+        assert_eq!(
+            NetV6::try_from((Ipv6Addr::UNSPECIFIED, 129)).unwrap_err(),
+            CidrError {
+                cidr: 129,
+                max: 128
+            }
+        );
+    }
+
+    #[test]
+    fn try_from_ip_addr_dispatches_on_family() {
+        let ip: IpAddr = "2001:db8::1".parse().unwrap();
+        match AnyNet::try_from((ip, 64)).unwrap() {
+            AnyNet::V6(net) => assert_eq!(
+                net.network_address(),
+                "2001:db8::".parse::<Ipv6Addr>().unwrap()
+            ),
+            AnyNet::V4(net) => panic!("expected an IPv6 network, got {:?}", net),
+        }
+        assert!(matches!(
+            AnyNet::try_from((IpAddr::V4(v4("10.0.0.0")), 8)),
+            Ok(AnyNet::V4(_))
+        ));
+        // IPv6 allows prefixes an IPv4 address doesn't
+        assert!(AnyNet::try_from((ip, 96)).is_ok());
+        assert!(AnyNet::try_from((IpAddr::V4(v4("10.0.0.0")), 64)).is_err());
+    }
+
+    #[test]
+    fn carve_splits_off_first_subnet() {
+        let net = NetV4::parse("10.0.0.0/24").unwrap();
+        let (first, rest) = net.carve(25).unwrap();
+        assert_eq!(first, NetV4::parse("10.0.0.0/25").unwrap());
+        assert_eq!(rest, vec![NetV4::parse("10.0.0.128/25").unwrap()]);
+    }
+
+    #[test]
+    fn carve_rejects_invalid_prefixes() {
+        let net = NetV4::parse("10.0.0.0/24").unwrap();
+        assert!(net.carve(24).is_none());
+        assert!(net.carve(33).is_none());
+    }
+
+    #[test]
+    fn carve_too_many_subnets() {
+        assert!(NetV4::parse("0.0.0.0/0").unwrap().carve(32).is_none());
+        assert!(NetV6::parse("::/0").unwrap().carve(128).is_none());
+    }
+
+    #[test]
+    fn try_new_cidr_range() {
+        assert!(NetV4::try_new(v4("10.0.0.0"), 33).is_err());
+        assert!(NetV4::try_new(v4("10.0.0.1"), 32).is_ok());
+        assert!(NetV6::try_new(Ipv6Addr::UNSPECIFIED, 129).is_err());
+    }
+
+    #[test]
+    fn try_new_at_ends_of_address_space() {
+        let net = NetV4::try_new(v4("0.0.0.0"), 32).unwrap();
+        assert_eq!(net.host(), (v4("0.0.0.0"), v4("0.0.0.0")));
+        let net = NetV4::try_new(v4("255.255.255.255"), 32).unwrap();
+        assert_eq!(net.host(), (v4("255.255.255.255"), v4("255.255.255.255")));
+        let net = NetV6::try_new(Ipv6Addr::UNSPECIFIED, 128).unwrap();
+        assert_eq!(net.host(), (Ipv6Addr::UNSPECIFIED, Ipv6Addr::UNSPECIFIED));
+    }
+
+    #[test]
+    fn try_new_host_range() {
+        let net = NetV4::try_new(v4("10.0.0.0"), 24).unwrap();
+        assert_eq!(net.host(), (v4("10.0.0.1"), v4("10.0.0.254")));
+        let net = NetV4::try_new(v4("255.255.255.254"), 31).unwrap();
+        assert_eq!(net.host(), (v4("255.255.255.254"), v4("255.255.255.255")));
+    }
+
+    fn nets_v4(texts: &[&str]) -> Vec<NetV4> {
+        texts
+            .iter()
+            .map(|text| NetV4::parse(text).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn cover_contiguous_addresses() {
+        let addrs = [
+            v4("10.0.0.2"),
+            v4("10.0.0.0"),
+            v4("10.0.0.3"),
+            v4("10.0.0.1"),
+        ];
+        assert_eq!(cover_addresses::<V4>(&addrs), nets_v4(&["10.0.0.0/30"]));
+    }
+
+    #[test]
+    fn cover_scattered_addresses() {
+        let addrs = [
+            v4("10.0.0.1"),
+            v4("10.0.0.5"),
+            v4("10.0.0.9"),
+            v4("10.0.0.5"),
+        ];
+        assert_eq!(
+            cover_addresses::<V4>(&addrs),
+            nets_v4(&["10.0.0.1/32", "10.0.0.5/32", "10.0.0.9/32"])
+        );
+    }
+
+    #[test]
+    fn cover_ends_of_address_space() {
+        assert_eq!(
+            cover_addresses::<V4>(&[v4("0.0.0.0")]),
+            nets_v4(&["0.0.0.0/32"])
+        );
+        assert_eq!(
+            cover_addresses::<V4>(&[v4("255.255.255.255")]),
+            nets_v4(&["255.255.255.255/32"])
+        );
+        assert!(cover_addresses::<V4>(&[]).is_empty());
+    }
+
+    #[test]
+    fn range_of_one_aligned_block() {
+        assert_eq!(
+            range_to_prefixes::<V4>(v4("10.0.0.0"), v4("10.0.0.255")),
+            nets_v4(&["10.0.0.0/24"])
+        );
+    }
+
+    #[test]
+    fn range_misaligned() {
+        assert_eq!(
+            range_to_prefixes::<V4>(v4("10.0.0.1"), v4("10.0.0.10")),
+            nets_v4(&[
+                "10.0.0.1/32",
+                "10.0.0.2/31",
+                "10.0.0.4/30",
+                "10.0.0.8/31",
+                "10.0.0.10/32"
+            ])
+        );
+        assert!(range_to_prefixes::<V4>(v4("10.0.0.1"), v4("10.0.0.0")).is_empty());
+    }
+
+    #[test]
+    fn range_ends_of_address_space() {
+        assert_eq!(
+            range_to_prefixes::<V4>(v4("255.255.255.255"), v4("255.255.255.255")),
+            nets_v4(&["255.255.255.255/32"])
+        );
+        assert_eq!(
+            range_to_prefixes::<V4>(v4("0.0.0.0"), v4("255.255.255.255")),
+            nets_v4(&["0.0.0.0/0"])
+        );
+    }
+
+    #[test]
+    fn is_subnet_of() {
+        let child = NetV4::parse("10.1.2.0/24").unwrap();
+        let parent = NetV4::parse("10.1.0.0/16").unwrap();
+        assert!(child.is_subnet_of(&parent));
+        assert!(!parent.is_subnet_of(&child));
+        assert!(parent.is_subnet_of(&parent));
+        assert!(!child.is_subnet_of(&NetV4::parse("10.2.0.0/16").unwrap()));
+    }
+
+    #[test]
+    fn hash_set_dedups_equal_networks() {
+        let mut nets = std::collections::HashSet::new();
+        nets.insert(NetV4::parse("10.0.0.0/24").unwrap());
+        nets.insert(NetV4::parse("10.0.0.37/24").unwrap());
+        assert_eq!(nets.len(), 1);
+        nets.insert(NetV4::parse("10.0.0.0/25").unwrap());
+        assert_eq!(nets.len(), 2);
+    }
+
+    #[test]
+    fn usable_hosts_agree_with_reserved_addresses() {
+        for (text, hosts) in [("10.0.0.0/24", 254), ("10.0.0.0/31", 2), ("10.0.0.1/32", 1)] {
+            let net = NetV4::parse(text).unwrap();
+            assert_eq!(net.usable_hosts(), hosts, "{}", text);
+            assert_eq!(
+                net.usable_hosts() + net.reserved_addresses().len() as u32,
+                1 << (32 - net.cidr()),
+                "{}",
+                text
+            );
+        }
+        assert_eq!(
+            NetV4::parse("10.0.0.0/24").unwrap().reserved_addresses(),
+            [v4("10.0.0.0"), v4("10.0.0.255")]
+        );
+    }
+
+    #[test]
+    fn usable_hosts_v6() {
+        let net = NetV6::parse("2001:db8::/64").unwrap();
+        assert_eq!(net.usable_hosts(), (1 << 64) - 2);
+        assert!(net.reserved_addresses().is_empty());
+    }
+
+    #[test]
+    fn network_address_radix() {
+        let net = NetV4::parse("192.168.1.0/24").unwrap();
+        assert_eq!(net.network_address_radix(Radix::Dec), "3232235776");
+        assert_eq!(net.network_address_radix(Radix::Hex), "0xC0A80100");
+        assert_eq!(
+            net.network_address_radix(Radix::Bin),
+            "0b11000000101010000000000100000000"
+        );
+        let net = NetV6::parse("2001:db8::/32").unwrap();
+        assert_eq!(
+            net.network_address_radix(Radix::Hex),
+            "0x20010DB8000000000000000000000000"
+        );
+    }
+
+    #[test]
+    fn has_host_bits() {
+        assert!(NetV4::parse("192.168.1.37/24").unwrap().has_host_bits());
+        assert!(!NetV4::parse("192.168.1.0/24").unwrap().has_host_bits());
+    }
+
+    #[test]
+    fn sn_table_matches_shift() {
+        for cidr in 0..=32 {
+            assert_eq!(
+                sn_from_cidr_u32(cidr),
+                sn_from_cidr_u32_shift(cidr),
+                "/{}",
+                cidr
+            );
+        }
+        assert_eq!(sn_from_cidr_u32(0), 0);
+        assert_eq!(sn_from_cidr_u32(24), 0xFFFF_FF00);
+        assert_eq!(sn_from_cidr_u32(32), u32::MAX);
+    }
+
+    #[test]
+    fn parse_net_error_mentions_net_format() {
+        let msg = NetV4::parse("garbage").unwrap_err().to_string();
+        assert_eq!(
+            msg,
+            r#"Could not parse "garbage", expected NET/CIDR, e.g. "10.0.0.0/24""#
+        );
+    }
+
+    #[test]
+    fn parse_empty_net_mentions_net_format() {
+        assert_eq!(NetV6::parse(" ").unwrap_err(), ParseNetError::Empty);
+        assert_eq!(
+            ParseNetError::Empty.to_string(),
+            r#"Input is empty, expected NET/CIDR, e.g. "10.0.0.0/24""#
+        );
     }
 }
@@ -0,0 +1,202 @@
+use colored::Colorize;
+
+use crate::{
+    net::{GenNet, IpInfo},
+    task::format_ip,
+};
+
+/// Receives the subnets of a split one by one, see [`crate::task::Task::render`].
+pub trait SubnetSink<Net: IpInfo> {
+    fn header(&mut self) {}
+    fn row(&mut self, idx: usize, net: &GenNet<Net>);
+    fn footer(&mut self) {}
+}
+
+pub struct MarkdownSink {
+    out: String,
+    verbose: bool,
+    v4_mapped: bool,
+}
+
+impl MarkdownSink {
+    pub fn new(verbose: bool, v4_mapped: bool) -> Self {
+        MarkdownSink {
+            out: "".to_owned(),
+            verbose,
+            v4_mapped,
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+impl<Net: IpInfo> SubnetSink<Net> for MarkdownSink {
+    fn header(&mut self) {
+        if self.verbose {
+            self.out.push_str(
+                "| Nr. | Netzadresse | Broadcastadresse | Erster Host | Letzter Host | Nutzbare Hosts |\n\
+                 |---|---|---|---|---|---|\n",
+            );
+        } else {
+            self.out.push_str(
+                "| Nr. | Netzadresse | Broadcastadresse | Hostbereich |\n\
+                 |---|---|---|---|\n",
+            );
+        }
+    }
+
+    fn row(&mut self, idx: usize, net: &GenNet<Net>) {
+        let (hfrom, hto) = net.host();
+        let na = format_ip::<Net>(net.network_address(), self.v4_mapped);
+        let bc = format_ip::<Net>(net.broadcast_address(), self.v4_mapped);
+        let hfrom = format_ip::<Net>(hfrom, self.v4_mapped);
+        let hto = format_ip::<Net>(hto, self.v4_mapped);
+        if self.verbose {
+            self.out.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} |\n",
+                idx + 1,
+                na,
+                bc,
+                hfrom,
+                hto,
+                net.usable_hosts()
+            ));
+        } else {
+            self.out.push_str(&format!(
+                "| {} | {} | {} | {} - {} |\n",
+                idx + 1,
+                na,
+                bc,
+                hfrom,
+                hto
+            ));
+        }
+    }
+}
+
+pub struct CsvSink {
+    out: String,
+    v4_mapped: bool,
+}
+
+impl CsvSink {
+    pub fn new(v4_mapped: bool) -> Self {
+        CsvSink {
+            out: "".to_owned(),
+            v4_mapped,
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+impl<Net: IpInfo> SubnetSink<Net> for CsvSink {
+    fn header(&mut self) {
+        self.out
+            .push_str("Nr,Netzadresse,Broadcastadresse,Erster Host,Letzter Host\n");
+    }
+
+    fn row(&mut self, idx: usize, net: &GenNet<Net>) {
+        let (hfrom, hto) = net.host();
+        self.out.push_str(&format!(
+            "{},{},{},{},{}\n",
+            idx + 1,
+            format_ip::<Net>(net.network_address(), self.v4_mapped),
+            format_ip::<Net>(net.broadcast_address(), self.v4_mapped),
+            format_ip::<Net>(hfrom, self.v4_mapped),
+            format_ip::<Net>(hto, self.v4_mapped)
+        ));
+    }
+
+    /// Ends the block with a blank line, so the output of several tasks can be
+    /// concatenated into an answer file for [`crate::grade::parse_answers`].
+    fn footer(&mut self) {
+        self.out.push('\n');
+    }
+}
+
+pub struct CliSink {
+    out: String,
+    v4_mapped: bool,
+}
+
+impl CliSink {
+    pub fn new(v4_mapped: bool) -> Self {
+        CliSink {
+            out: "".to_owned(),
+            v4_mapped,
+        }
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+impl<Net: IpInfo> SubnetSink<Net> for CliSink {
+    fn row(&mut self, _idx: usize, net: &GenNet<Net>) {
+        let (hfrom, hto) = net.host();
+        self.out.push_str(&format!(
+            "NA: {} BC: {} Host: {} - {}\n",
+            format_ip::<Net>(net.network_address(), self.v4_mapped).yellow(),
+            format_ip::<Net>(net.broadcast_address(), self.v4_mapped).purple(),
+            format_ip::<Net>(hfrom, self.v4_mapped).on_yellow(),
+            format_ip::<Net>(hto, self.v4_mapped).on_yellow()
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        grade::parse_answers,
+        net::V4,
+        task::{TaskGen, TaskV4},
+    };
+
+    #[derive(Default)]
+    struct MockSink {
+        calls: Vec<String>,
+    }
+
+    impl SubnetSink<V4> for MockSink {
+        fn header(&mut self) {
+            self.calls.push("header".to_owned());
+        }
+
+        fn row(&mut self, idx: usize, net: &GenNet<V4>) {
+            self.calls
+                .push(format!("{} {}/{}", idx, net.network_address(), net.cidr()));
+        }
+
+        fn footer(&mut self) {
+            self.calls.push("footer".to_owned());
+        }
+    }
+
+    #[test]
+    fn render_calls_sink_in_order() {
+        let mut sink = MockSink::default();
+        TaskV4::parse("10.0.0.0/28 -> 2").unwrap().render(&mut sink);
+        assert_eq!(
+            sink.calls,
+            ["header", "0 10.0.0.0/29", "1 10.0.0.8/29", "footer"]
+        );
+    }
+
+    #[test]
+    fn csv_blocks_concatenate_into_answer_file() {
+        let csv = ["10.0.0.0/28 -> 2", "10.0.0.0/24 -> 4"]
+            .map(|text| TaskV4::parse(text).unwrap().to_csv(false))
+            .concat();
+        let answers = parse_answers(&csv);
+        assert_eq!(answers.len(), 2);
+        assert_eq!(answers[0].len(), 2);
+        assert_eq!(answers[1].len(), 4);
+    }
+}
@@ -1,13 +1,18 @@
 use rand::Rng;
 use regex::Regex;
 
-use crate::net::{self, GenNet, IpByteTypeHelper, IpInfo, IpParse, NetV4, NetV6};
-use colored::Colorize;
+use crate::{
+    net::{self, GenNet, IpByteTypeHelper, IpInfo, IpParse, IpTrait, NetV4, NetV6, ParseNetError},
+    sink::{CliSink, CsvSink, MarkdownSink, SubnetSink},
+};
+use colored::{Color, Colorize};
 use std::{
-    cmp::min,
-    fmt::Debug,
+    cmp::{max, min},
+    error::Error,
+    fmt::{Debug, Display},
+    iter,
     net::{Ipv4Addr, Ipv6Addr},
-    ops::{BitAnd, Not},
+    ops::{BitAnd, Not, RangeInclusive},
 };
 
 #[derive(Debug)]
@@ -19,20 +24,109 @@ pub struct Task<Net: IpInfo> {
 pub type TaskV4 = Task<net::V4>;
 pub type TaskV6 = Task<net::V6>;
 
+/// Padding of the address and CIDR columns in task listings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaskColumns {
+    pub ip: usize,
+    pub cidr: usize,
+}
+
+impl TaskColumns {
+    /// Widths that align all `tasks` to the longest address and CIDR.
+    pub fn fit<Net: IpInfo>(tasks: &[Task<Net>]) -> Self {
+        tasks
+            .iter()
+            .fold(TaskColumns { ip: 0, cidr: 0 }, |cols, task| TaskColumns {
+                ip: max(cols.ip, task.network.initial_ip().to_string().len()),
+                cidr: max(cols.cidr, task.network.cidr().to_string().len()),
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MarkdownOptions {
+    /// Markdown heading level of the task, clamped to 1..=6.
+    pub heading_level: u8,
+    pub page_breaks: bool,
+    /// Print IPv4-mapped IPv6 addresses as `::ffff:a.b.c.d`, see [`format_ip`].
+    pub v4_mapped: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        MarkdownOptions {
+            heading_level: 2,
+            page_breaks: true,
+            v4_mapped: false,
+        }
+    }
+}
+
+const PAGE_BREAK: &str = r#"<div style="page-break-after: always;"></div>"#;
+
+/// Worksheet with all task prompts first and all solutions after a page break.
+pub fn combined_markdown<Net: IpInfo>(tasks: &[Task<Net>]) -> String {
+    let cols = TaskColumns::fit(tasks);
+    let mut md = "# Aufgaben\n\n```\n".to_owned();
+    for (i, task) in tasks.iter().enumerate() {
+        md.push_str(&task.task_text(Some(i as u32 + 1), cols));
+        md.push('\n');
+    }
+    md.push_str("```\n\n");
+    md.push_str(PAGE_BREAK);
+    md.push_str("\n\n# Lösungen\n\n");
+    for (i, task) in tasks.iter().enumerate() {
+        md.push_str(&task.to_markdown(Some(i as u32 + 1), MarkdownOptions::default()));
+        md.push('\n');
+    }
+    md
+}
+
+/// Formats `ip`, forcing the `::ffff:a.b.c.d` form for IPv4-mapped IPv6
+/// addresses (`::ffff:0:0/96`) if `v4_mapped` is set.
+pub fn format_ip<Net: IpInfo>(ip: Net::IpType, v4_mapped: bool) -> String {
+    match ip.to_ipv4_mapped() {
+        Some(v4) if v4_mapped => format!("::ffff:{}", v4),
+        _ => format!("{}", ip),
+    }
+}
+
+/// Number of prefix bits needed to split a network into at least `count`
+/// subnets, i.e. the exponent of the next power of two.
+pub fn bits_for_subnets(count: u32) -> u8 {
+    match count.checked_next_power_of_two() {
+        Some(num) => num.ilog2() as u8,
+        None => u32::BITS as u8,
+    }
+}
+
 impl<Net: IpInfo> Task<Net> {
+    /// Fails if the split needs a longer prefix than the address has, like
+    /// `10.0.0.0/30 -> 8`.
+    fn check_target(self) -> Result<Self, ParseNetError> {
+        if self.target_cidr() > Net::Bits::BITS {
+            return Err(ParseNetError::TooManySubnets {
+                subnets: self.subnets,
+                target_cidr: self.target_cidr(),
+                max: Net::Bits::BITS,
+            });
+        }
+        Ok(self)
+    }
+
     pub fn target_cidr(&self) -> u8 {
-        let num = if self.subnets.is_power_of_two() {
-            self.subnets
-        } else {
-            self.subnets.next_power_of_two()
-        };
+        bits_for_subnets(self.subnets) + self.network.cidr()
+    }
 
-        let n = num.ilog2().try_into().unwrap_or(self.network.cidr() + 2);
-        n + self.network.cidr()
+    /// Number of subnets after the split. Wider than `u32`, since splitting
+    /// an IPv4 `/0` into `/32`s gives 2^32 subnets.
+    pub fn new_subnets(&self) -> u64 {
+        1 << (self.target_cidr() - self.network.cidr())
     }
 
-    pub fn new_subnets(&self) -> u32 {
-        2u32.pow((self.target_cidr() - self.network.cidr()).into())
+    /// Index of the last subnet, fits `Net::Bits` for every split.
+    fn last_subnet(&self) -> Net::Bits {
+        Net::Bits::from((self.new_subnets() - 1) as u32)
     }
 
     pub fn target_subnetmask_u32(&self) -> u32 {
@@ -47,60 +141,226 @@ impl<Net: IpInfo> Task<Net> {
     where
         <<Net as IpInfo>::Bits as Not>::Output: BitAnd<<Net as IpInfo>::Bits>,
     {
+        self.target_networks_iter().collect()
+    }
+
+    /// Builds the subnet with a given index. The target CIDR and mask are
+    /// computed once, not per subnet.
+    fn subnet_builder(&self) -> impl Fn(Net::Bits) -> GenNet<Net> {
         let tcidr = self.target_cidr();
         let sna = self.network.network_address_bits();
+        let mask = net::subnet_index_mask::<Net>(self.network.subnetmask_bits(), tcidr);
 
-        let networks = Net::Bits::pow(2, (tcidr - self.network.cidr()).into());
+        move |i| GenNet::<Net>::new(Net::calc_masked_subnet_address(sna, mask, tcidr, i), tcidr)
+    }
 
-        let mut nas = vec![];
+    pub fn target_networks_iter(&self) -> impl Iterator<Item = GenNet<Net>> {
+        (Net::Bits::ZERO..=self.last_subnet()).map(self.subnet_builder())
+    }
 
-        for i in Net::Bits::ZERO..networks {
-            let na = Net::calc_subnet_address(
-                sna,
-                self.network.subnetmask_bits(),
-                self.target_cidr(),
-                i,
-            );
-            nas.push(GenNet::<Net>::new(na, tcidr))
+    /// Yields every `step`th subnet, starting with the first one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is zero.
+    pub fn target_networks_step(&self, step: Net::Bits) -> impl Iterator<Item = GenNet<Net>> {
+        assert!(step != Net::Bits::ZERO, "step must not be zero");
+        let last = self.last_subnet();
+
+        iter::successors(Some(Net::Bits::ZERO), move |&i| {
+            if last - i >= step {
+                Some(i + step)
+            } else {
+                None
+            }
+        })
+        .map(self.subnet_builder())
+    }
+
+    pub fn subnet_at(&self, idx: Net::Bits) -> GenNet<Net> {
+        self.subnet_builder()(idx)
+    }
+
+    /// Draws the base network as a ruler `width` columns wide with one bar per
+    /// subnet. If there are more subnets than columns, neighbouring subnets are
+    /// merged into one bar.
+    pub fn ascii_map(&self, width: usize, v4_mapped: bool) -> String {
+        let width = width.clamp(1, u32::MAX as usize) as u64;
+        let subnets = self.new_subnets();
+        let segments = min(subnets, width);
+        let subnet = self.subnet_builder();
+
+        // Index of the first subnet in a segment, without overflowing for huge splits
+        let first_of =
+            |seg: u64| seg * (subnets / segments) + seg * (subnets % segments) / segments;
+
+        let mut ruler = "".to_owned();
+        let mut bars = "".to_owned();
+        for seg in 0..segments {
+            let col_from = seg * width / segments;
+            let col_until = (seg + 1) * width / segments;
+            let first = first_of(seg);
+            let last = first_of(seg + 1) - 1;
+
+            ruler.push('|');
+            ruler.push_str(&"-".repeat((col_until - col_from - 1) as usize));
+
+            let label = if first == last {
+                format!("{}", first + 1)
+            } else {
+                format!("{}-{}", first + 1, last + 1)
+            };
+            let (hfrom, _) = subnet(Net::Bits::from(first as u32)).host();
+            let (_, hto) = subnet(Net::Bits::from(last as u32)).host();
+            bars.push_str(&format!(
+                "{}{}{}  {}: {} - {}\n",
+                " ".repeat(col_from as usize),
+                "#".repeat((col_until - col_from) as usize),
+                " ".repeat((width - col_until) as usize),
+                label,
+                format_ip::<Net>(hfrom, v4_mapped),
+                format_ip::<Net>(hto, v4_mapped)
+            ));
+        }
+
+        format!("{}\n{}", ruler, bars)
+    }
+
+    pub fn render<S: SubnetSink<Net>>(&self, sink: &mut S) {
+        sink.header();
+        for (idx, network) in self.target_networks_iter().enumerate() {
+            sink.row(idx, &network);
         }
-        nas
+        sink.footer();
     }
 
-    pub fn print_task_cli(&self, idx: Option<u32>) {
-        let idx_str = if let Some(idx) = idx {
+    pub fn subnets_markdown_table_verbose(&self, v4_mapped: bool) -> String {
+        let mut sink = MarkdownSink::new(true, v4_mapped);
+        self.render(&mut sink);
+        sink.finish()
+    }
+
+    pub fn to_csv(&self, v4_mapped: bool) -> String {
+        let mut sink = CsvSink::new(v4_mapped);
+        self.render(&mut sink);
+        sink.finish()
+    }
+
+    pub fn summary_line(&self, v4_mapped: bool) -> String {
+        format!(
+            "{}/{} -> {} subnets (/{}, {} hosts each)",
+            format_ip::<Net>(self.network.initial_ip(), v4_mapped),
+            self.network.cidr(),
+            self.new_subnets(),
+            self.target_cidr(),
+            self.subnet_at(Net::Bits::ZERO).usable_hosts()
+        )
+    }
+
+    fn idx_prefix(idx: Option<u32>) -> String {
+        if let Some(idx) = idx {
             format!("{:4}.: ", idx)
         } else {
             "".to_owned()
+        }
+    }
+
+    /// Task line as printed by `gen`, e.g. `   1.: 10.0.0.0/24 -> 4`.
+    fn format_task(&self, idx: Option<u32>, cols: TaskColumns, colored: bool) -> String {
+        let paint = |text: String, color: Color| {
+            if colored {
+                text.color(color).to_string()
+            } else {
+                text
+            }
         };
-        println!(
+        format!(
             "{}{}/{} -> {}",
-            idx_str.red(),
-            format!("{:>15}", self.network.initial_ip()).blue(),
-            format!("{:<2}", self.network.cidr()).green(),
-            format!("{}", self.subnets).cyan()
+            paint(Self::idx_prefix(idx), Color::Red),
+            paint(
+                format!("{:>width$}", self.network.initial_ip(), width = cols.ip),
+                Color::Blue
+            ),
+            paint(
+                format!("{:<width$}", self.network.cidr(), width = cols.cidr),
+                Color::Green
+            ),
+            paint(format!("{}", self.subnets), Color::Cyan)
+        )
+    }
+
+    pub fn print_task_cli(&self, idx: Option<u32>, cols: TaskColumns) {
+        println!("{}", self.format_task(idx, cols, true));
+    }
+
+    pub fn task_text(&self, idx: Option<u32>, cols: TaskColumns) -> String {
+        self.format_task(idx, cols, false)
+    }
+
+    pub fn to_markdown(&self, idx: Option<u32>, options: MarkdownOptions) -> String {
+        let heading = if let Some(idx) = idx {
+            format!("Aufgabe {}: ", idx)
+        } else {
+            "".to_owned()
+        };
+        let (hfrom, hto) = self.network.host();
+        let v4_mapped = options.v4_mapped;
+
+        let mut md = format!(
+            r#"{} {}{}/{} -> {}
+
+- Netzadresse: {}
+- Subnetzmaske: {}
+- Broadcastadresse: {}
+- Hostbereich: {} - {}
+- Aufgeteilt in {} Netze mit der Subnetzmaske {} und dem CIDR {}
+
+"#,
+            "#".repeat(options.heading_level.clamp(1, 6).into()),
+            heading,
+            format_ip::<Net>(self.network.initial_ip(), v4_mapped),
+            self.network.cidr(),
+            self.subnets,
+            format_ip::<Net>(self.network.network_address(), v4_mapped),
+            self.network.subnetmask(),
+            format_ip::<Net>(self.network.broadcast_address(), v4_mapped),
+            format_ip::<Net>(hfrom, v4_mapped),
+            format_ip::<Net>(hto, v4_mapped),
+            self.new_subnets(),
+            self.target_subnetmask(),
+            self.target_cidr(),
         );
+        let mut sink = MarkdownSink::new(false, v4_mapped);
+        self.render(&mut sink);
+        md.push_str(&sink.finish());
+        if options.page_breaks {
+            md.push('\n');
+            md.push_str(PAGE_BREAK);
+            md.push('\n');
+        }
+        md
     }
 
-    pub fn print_cli(&self)
+    pub fn print_cli(&self, v4_mapped: bool)
     where
         <<Net as IpInfo>::Bits as Not>::Output: BitAnd<<Net as IpInfo>::Bits>,
     {
-        let mut nets = "".to_owned();
-        for network in self.target_networks().iter() {
-            let (hfrom, hto) = network.host();
-            nets.push_str(&format!(
-                "NA: {} BC: {} Host: {} - {}\n",
-                format!("{}", network.network_address()).yellow(),
-                format!("{}", network.broadcast_address()).purple(),
-                format!("{}", hfrom).on_yellow(),
-                format!("{}", hto).on_yellow()
-            ));
-        }
+        let mut sink = CliSink::new(v4_mapped);
+        self.render(&mut sink);
+        let nets = sink.finish();
 
         let (hfrom, hto) = self.network.host();
+        let note = if self.network.has_host_bits() {
+            format!(
+                "\n{}",
+                "Hinweis: Host-Bits gesetzt, Netzadresse berechnet.".red()
+            )
+        } else {
+            "".to_owned()
+        };
         println!(
             r#"
-{}/{} in {} Subnetze unterteilen
+{}/{} in {} Subnetze unterteilen{}
 Netzadresse: {}
 Subnetzmaske: {}
 Broadcastadresse: {}
@@ -109,14 +369,15 @@ Aufgeteilt in {} Netze mit der Subnetzmaske {} und dem CIDR {}
 
 Netzwerke
 {}"#,
-            format!("{}", self.network.initial_ip()).blue(),
+            format_ip::<Net>(self.network.initial_ip(), v4_mapped).blue(),
             format!("{}", self.network.cidr()).green(),
             format!("{}", self.subnets).cyan(),
-            format!("{}", self.network.network_address()).yellow(),
+            note,
+            format_ip::<Net>(self.network.network_address(), v4_mapped).yellow(),
             format!("{}", self.network.subnetmask()).green(),
-            format!("{}", self.network.broadcast_address()).purple(),
-            format!("{}", hfrom).on_yellow(),
-            format!("{}", hto).on_yellow(),
+            format_ip::<Net>(self.network.broadcast_address(), v4_mapped).purple(),
+            format_ip::<Net>(hfrom, v4_mapped).on_yellow(),
+            format_ip::<Net>(hto, v4_mapped).on_yellow(),
             format!("{}", self.new_subnets()).cyan(),
             format!("{}", self.target_subnetmask()).green(),
             format!("{}", self.target_cidr()).green(),
@@ -125,61 +386,148 @@ Netzwerke
     }
 }
 
-pub trait TaskGen<Net: IpInfo> {
-    fn rand(min_subnets: u32, max_subnets: u32, min_cidr: u8, max_cidr: u8) -> Self;
-    fn parse(text: &str) -> Self;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenError {
+    CidrTooBig { cidr: u8, max: u8 },
+    EmptyCidrRange { min: u8, max: u8 },
+    EmptySubnetRange { min: u32, max: u32 },
 }
 
-impl TaskGen<net::V4> for Task<net::V4> {
-    fn rand(min_subnets: u32, max_subnets: u32, min_cidr: u8, max_cidr: u8) -> Self {
-        if min_cidr > 32 || max_cidr > 32 {
-            panic!()
+impl Display for GenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GenError::CidrTooBig { cidr, max } => {
+                write!(f, "CIDR /{} is too big, at most /{} is allowed", cidr, max)
+            }
+            GenError::EmptyCidrRange { min, max } => {
+                write!(f, "No CIDR between /{} and /{} can be generated", min, max)
+            }
+            GenError::EmptySubnetRange { min, max } => write!(
+                f,
+                "No subnet count between {} and {} can be generated",
+                min, max
+            ),
         }
+    }
+}
+
+impl Error for GenError {}
+
+fn check_cidr_range(min_cidr: u8, max_cidr: u8, limit: u8) -> Result<(), GenError> {
+    if let Some(cidr) = [min_cidr, max_cidr].into_iter().find(|&c| c > limit) {
+        return Err(GenError::CidrTooBig { cidr, max: limit });
+    }
+    if min_cidr > max_cidr {
+        return Err(GenError::EmptyCidrRange {
+            min: min_cidr,
+            max: max_cidr,
+        });
+    }
+    Ok(())
+}
+
+/// Range of subnet counts for a network with prefix `source`, when at most
+/// `max_bits` of the address may be used for network and subnet.
+fn subnet_range(
+    min_subnets: u32,
+    max_subnets: u32,
+    source: u8,
+    max_bits: u8,
+) -> Result<RangeInclusive<u32>, GenError> {
+    let top = max_bits
+        .checked_sub(source)
+        .map_or(0, |bits| 2u32.checked_pow(bits.into()).unwrap_or(u32::MAX));
+    let top = min(top, max_subnets);
+    if min_subnets > top {
+        return Err(GenError::EmptySubnetRange {
+            min: min_subnets,
+            max: top,
+        });
+    }
+    Ok(min_subnets..=top)
+}
+
+/// Prefixes in `min_cidr..=max_cidr` that can still be split into at least
+/// `min_subnets` subnets, see [`subnet_range`].
+fn source_range(
+    min_subnets: u32,
+    max_subnets: u32,
+    min_cidr: u8,
+    max_cidr: u8,
+    max_bits: u8,
+) -> Result<RangeInclusive<u8>, GenError> {
+    subnet_range(min_subnets, max_subnets, min_cidr, max_bits)?;
+    let max_source = (min_cidr..=max_cidr)
+        .rev()
+        .find(|&source| subnet_range(min_subnets, max_subnets, source, max_bits).is_ok())
+        .unwrap_or(min_cidr);
+    Ok(min_cidr..=max_source)
+}
+
+pub trait TaskGen<Net: IpInfo>: Sized {
+    fn rand(
+        min_subnets: u32,
+        max_subnets: u32,
+        min_cidr: u8,
+        max_cidr: u8,
+    ) -> Result<Self, GenError>;
+    fn parse(text: &str) -> Result<Self, ParseNetError>;
+}
+
+impl TaskGen<net::V4> for Task<net::V4> {
+    fn rand(
+        min_subnets: u32,
+        max_subnets: u32,
+        min_cidr: u8,
+        max_cidr: u8,
+    ) -> Result<Self, GenError> {
+        check_cidr_range(min_cidr, max_cidr, 32)?;
+        let sources = source_range(min_subnets, max_subnets, min_cidr, max_cidr, 30)?;
 
         let mut rng = rand::thread_rng();
-        let source: u8 = rng.gen_range(min_cidr..=max_cidr);
-        let target: u32 = rng
-            .gen_range(min_subnets..=min(2u32.pow((30 - source).into()), max_subnets))
-            .try_into()
-            .unwrap_or(32);
+        let source: u8 = rng.gen_range(sources);
+        let target: u32 = rng.gen_range(subnet_range(min_subnets, max_subnets, source, 30)?);
         let ip = Ipv4Addr::new(rng.gen(), rng.gen(), rng.gen(), rng.gen());
-        Task {
+        Ok(Task {
             network: NetV4::new(ip, source),
             subnets: target,
-        }
+        })
     }
 
-    fn parse(text: &str) -> Self {
+    fn parse(text: &str) -> Result<Self, ParseNetError> {
+        net::check_empty(text).map_err(|_| ParseNetError::EmptyTask)?;
+        let malformed = || ParseNetError::MalformedTask(text.to_owned());
         let re = Regex::new(
-            r"^(?P<net>\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}/\d{1,2})\s->\s(?P<networks>\d+).?$",
+            r"^(?P<net>\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}(/\d{1,2})?)\s+->\s+(?P<networks>\d+)$",
         )
         .unwrap();
-        let caps = re.captures(text).unwrap();
+        let caps = re.captures(text).ok_or_else(malformed)?;
         let net = caps.name("net").unwrap();
         let networks = caps.name("networks").unwrap();
 
         Task {
-            network: NetV4::parse(net.as_str()),
-            subnets: networks.as_str().parse().unwrap(),
+            network: NetV4::parse(net.as_str())?,
+            subnets: networks.as_str().parse().map_err(|_| malformed())?,
         }
+        .check_target()
     }
 }
 
 impl TaskGen<net::V6> for Task<net::V6> {
-    fn rand(min_subnets: u32, max_subnets: u32, min_cidr: u8, max_cidr: u8) -> Self {
-        let max_cidr = min(62, max_cidr);
-        if min_cidr > 128 || max_cidr > 128 {
-            panic!()
-        }
+    fn rand(
+        min_subnets: u32,
+        max_subnets: u32,
+        min_cidr: u8,
+        max_cidr: u8,
+    ) -> Result<Self, GenError> {
+        check_cidr_range(min_cidr, max_cidr, 128)?;
+        let sources = source_range(min_subnets, max_subnets, min_cidr, max_cidr, 64)?;
         let mut rng = rand::thread_rng();
         for _ in 0..1000 {
-            let _ = rng.gen_range(min_cidr..=max_cidr);
+            let _ = rng.gen_range(sources.clone());
         }
-        let source: u8 = rng.gen_range(min_cidr..=max_cidr);
-        let target: u32 = rng
-            .gen_range(min_subnets..=min(2u32.pow((64 - source).into()), max_subnets))
-            .try_into()
-            .unwrap_or(32);
+        let source: u8 = rng.gen_range(sources);
+        let target: u32 = rng.gen_range(subnet_range(min_subnets, max_subnets, source, 64)?);
         let ip = Ipv6Addr::new(
             rng.gen(),
             rng.gen(),
@@ -190,21 +538,294 @@ impl TaskGen<net::V6> for Task<net::V6> {
             rng.gen(),
             rng.gen(),
         );
-        Task {
+        Ok(Task {
             network: NetV6::new(ip, source),
             subnets: target,
-        }
+        })
     }
 
-    fn parse(text: &str) -> Self {
-        let re = Regex::new(r"^(?P<net>(([0-9a-fA-F]{1,4}:){7,7}[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,7}:|([0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,5}(:[0-9a-fA-F]{1,4}){1,2}|([0-9a-fA-F]{1,4}:){1,4}(:[0-9a-fA-F]{1,4}){1,3}|([0-9a-fA-F]{1,4}:){1,3}(:[0-9a-fA-F]{1,4}){1,4}|([0-9a-fA-F]{1,4}:){1,2}(:[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:((:[0-9a-fA-F]{1,4}){1,6})|:((:[0-9a-fA-F]{1,4}){1,7}|:)|fe80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}|::(ffff(:0{1,4}){0,1}:){0,1}((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])|([0-9a-fA-F]{1,4}:){1,4}:((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9]))/\d{1,2})\s->\s(?P<networks>\d+).?$").unwrap();
-        let caps = re.captures(text).unwrap();
+    fn parse(text: &str) -> Result<Self, ParseNetError> {
+        net::check_empty(text).map_err(|_| ParseNetError::EmptyTask)?;
+        let malformed = || ParseNetError::MalformedTask(text.to_owned());
+        let re = Regex::new(r"^(?P<net>(([0-9a-fA-F]{1,4}:){7,7}[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,7}:|([0-9a-fA-F]{1,4}:){1,6}:[0-9a-fA-F]{1,4}|([0-9a-fA-F]{1,4}:){1,5}(:[0-9a-fA-F]{1,4}){1,2}|([0-9a-fA-F]{1,4}:){1,4}(:[0-9a-fA-F]{1,4}){1,3}|([0-9a-fA-F]{1,4}:){1,3}(:[0-9a-fA-F]{1,4}){1,4}|([0-9a-fA-F]{1,4}:){1,2}(:[0-9a-fA-F]{1,4}){1,5}|[0-9a-fA-F]{1,4}:((:[0-9a-fA-F]{1,4}){1,6})|:((:[0-9a-fA-F]{1,4}){1,7}|:)|fe80:(:[0-9a-fA-F]{0,4}){0,4}%[0-9a-zA-Z]{1,}|::(ffff(:0{1,4}){0,1}:){0,1}((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])|([0-9a-fA-F]{1,4}:){1,4}:((25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9])\.){3,3}(25[0-5]|(2[0-4]|1{0,1}[0-9]){0,1}[0-9]))/\d{1,3})\s+->\s+(?P<networks>\d+)$").unwrap();
+        let caps = re.captures(text).ok_or_else(malformed)?;
         let net = caps.name("net").unwrap();
         let networks = caps.name("networks").unwrap();
 
         Task {
-            network: NetV6::parse(net.as_str()),
-            subnets: networks.as_str().parse().unwrap(),
+            network: NetV6::parse(net.as_str())?,
+            subnets: networks.as_str().parse().map_err(|_| malformed())?,
         }
+        .check_target()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task_v4(text: &str) -> TaskV4 {
+        TaskV4::parse(text).unwrap()
+    }
+
+    #[test]
+    fn ascii_map_one_bar_per_subnet() {
+        let map = task_v4("10.0.0.0/24 -> 4").ascii_map(32, false);
+        let lines: Vec<&str> = map.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "|-------|-------|-------|-------");
+        assert_eq!(lines[0].matches('|').count(), 4);
+        assert_eq!(
+            lines[1],
+            "########                          1: 10.0.0.1 - 10.0.0.62"
+        );
+        assert_eq!(
+            lines[4],
+            "                        ########  4: 10.0.0.193 - 10.0.0.254"
+        );
+    }
+
+    #[test]
+    fn verbose_table_lists_usable_hosts() {
+        let table = task_v4("10.0.0.0/28 -> 2").subnets_markdown_table_verbose(false);
+        assert_eq!(
+            table,
+            "| Nr. | Netzadresse | Broadcastadresse | Erster Host | Letzter Host | Nutzbare Hosts |\n\
+             |---|---|---|---|---|---|\n\
+             | 1 | 10.0.0.0 | 10.0.0.7 | 10.0.0.1 | 10.0.0.6 | 6 |\n\
+             | 2 | 10.0.0.8 | 10.0.0.15 | 10.0.0.9 | 10.0.0.14 | 6 |\n"
+        );
+    }
+
+    #[test]
+    fn v4_mapped_addresses() {
+        let task = TaskV6::parse("::ffff:192.0.2.0/120 -> 2").unwrap();
+        assert_eq!(
+            task.to_csv(true),
+            "Nr,Netzadresse,Broadcastadresse,Erster Host,Letzter Host\n\
+             1,::ffff:192.0.2.0,::ffff:192.0.2.127,::ffff:192.0.2.1,::ffff:192.0.2.126\n\
+             2,::ffff:192.0.2.128,::ffff:192.0.2.255,::ffff:192.0.2.129,::ffff:192.0.2.254\n\n"
+        );
+    }
+
+    #[test]
+    fn combined_markdown_prompts_before_solutions() {
+        let tasks = vec![task_v4("10.0.0.0/24 -> 4"), task_v4("192.168.0.0/16 -> 2")];
+        let md = combined_markdown(&tasks);
+        let prompts = md.find("# Aufgaben").unwrap();
+        let solutions = md.find("# Lösungen").unwrap();
+        assert!(prompts < solutions);
+        assert!(md[prompts..solutions].contains("   1.:    10.0.0.0/24 -> 4\n"));
+        assert!(md[prompts..solutions].contains("   2.: 192.168.0.0/16 -> 2\n"));
+        assert!(md[solutions..].contains("## Aufgabe 2: 192.168.0.0/16 -> 2"));
+    }
+
+    #[test]
+    fn summary_line_format() {
+        assert_eq!(
+            task_v4("10.0.0.0/24 -> 4").summary_line(false),
+            "10.0.0.0/24 -> 4 subnets (/26, 62 hosts each)"
+        );
+    }
+
+    #[test]
+    fn target_networks_step_skips_subnets() {
+        let task = task_v4("10.0.0.0/16 -> 256");
+        let nets: Vec<_> = task
+            .target_networks_step(64)
+            .map(|net| net.network_address().to_string())
+            .collect();
+        assert_eq!(nets, ["10.0.0.0", "10.0.64.0", "10.0.128.0", "10.0.192.0"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "step must not be zero")]
+    fn target_networks_step_zero_panics() {
+        let _ = task_v4("10.0.0.0/16 -> 256").target_networks_step(0);
+    }
+
+    #[test]
+    fn rand_only_draws_splittable_sources() {
+        for _ in 0..200 {
+            let task = TaskV4::rand(2, 32, 16, 31).unwrap();
+            assert!(task.network.cidr() <= 29);
+            assert!(task.target_cidr() <= 30);
+        }
+    }
+
+    #[test]
+    fn rand_source_without_room_for_subnets() {
+        assert_eq!(
+            TaskV6::rand(2, 16, 70, 70).unwrap_err(),
+            GenError::EmptySubnetRange { min: 2, max: 0 }
+        );
+    }
+
+    #[test]
+    fn rand_v6_cidr_too_big() {
+        assert_eq!(
+            TaskV6::rand(2, 16, 16, 200).unwrap_err(),
+            GenError::CidrTooBig {
+                cidr: 200,
+                max: 128
+            }
+        );
+    }
+
+    #[test]
+    fn rand_min_subnets_above_max_subnets() {
+        assert_eq!(
+            TaskV4::rand(5, 2, 16, 28).unwrap_err(),
+            GenError::EmptySubnetRange { min: 5, max: 2 }
+        );
+    }
+
+    #[test]
+    fn task_columns_align_arrows() {
+        let tasks: Vec<TaskV6> = [
+            "::/8 -> 4",
+            "2001:db8:1234:5678::/64 -> 16",
+            "fe80::/10 -> 100",
+        ]
+        .into_iter()
+        .map(|text| TaskV6::parse(text).unwrap())
+        .collect();
+        let cols = TaskColumns::fit(&tasks);
+        let columns: Vec<usize> = tasks
+            .iter()
+            .enumerate()
+            .map(|(i, task)| task.task_text(Some(i as u32 + 1), cols).find("->").unwrap())
+            .collect();
+        assert!(columns.iter().all(|&col| col == columns[0]));
+    }
+
+    #[test]
+    fn parse_padded_task() {
+        let task = task_v4("10.0.0.0/8  -> 4");
+        assert_eq!(task.target_cidr(), 10);
+        assert!(TaskV6::parse("2001:db8::/32   ->  4").is_ok());
+    }
+
+    #[test]
+    fn split_v4_into_2_pow_32_subnets() {
+        let task = task_v4("0.0.0.0/0 -> 4000000000");
+        assert_eq!(task.new_subnets(), 1 << 32);
+        assert_eq!(
+            task.summary_line(false),
+            "0.0.0.0/0 -> 4294967296 subnets (/32, 1 hosts each)"
+        );
+        let last = task.subnet_at(task.last_subnet());
+        assert_eq!(last.network_address(), Ipv4Addr::new(255, 255, 255, 255));
+        assert_eq!(task.ascii_map(4, false).lines().count(), 5);
+    }
+
+    #[test]
+    fn split_v6_32_into_many_subnets() {
+        let task = TaskV6::parse("2001:db8::/32 -> 4000000000").unwrap();
+        assert_eq!(task.new_subnets(), 1 << 32);
+        assert_eq!(task.target_cidr(), 64);
+        let nets: Vec<_> = task
+            .target_networks_iter()
+            .take(2)
+            .map(|net| net.network_address().to_string())
+            .collect();
+        assert_eq!(nets, ["2001:db8::", "2001:db8:0:1::"]);
+        let last = task.subnet_at(task.last_subnet());
+        assert_eq!(last.network_address().to_string(), "2001:db8:ffff:ffff::");
+    }
+
+    #[test]
+    fn to_markdown_options() {
+        let task = task_v4("10.0.0.0/24 -> 4");
+        let md = task.to_markdown(
+            Some(1),
+            MarkdownOptions {
+                heading_level: 3,
+                page_breaks: false,
+                v4_mapped: false,
+            },
+        );
+        assert!(md.starts_with("### Aufgabe 1: 10.0.0.0/24 -> 4\n"));
+        assert!(!md.contains(PAGE_BREAK));
+
+        let md = task.to_markdown(
+            None,
+            MarkdownOptions {
+                heading_level: 9,
+                ..MarkdownOptions::default()
+            },
+        );
+        assert!(md.starts_with("###### 10.0.0.0/24 -> 4\n"));
+        assert!(md.contains(PAGE_BREAK));
+    }
+
+    #[test]
+    fn bits_for_subnet_counts() {
+        let bits: Vec<u8> = [1, 2, 3, 4, 5, 8, 9]
+            .into_iter()
+            .map(bits_for_subnets)
+            .collect();
+        assert_eq!(bits, [0, 1, 2, 2, 3, 3, 4]);
+        assert_eq!(bits_for_subnets(u32::MAX), 32);
+    }
+
+    #[test]
+    fn parse_empty_and_malformed_tasks() {
+        assert_eq!(TaskV4::parse("").unwrap_err(), ParseNetError::EmptyTask);
+        assert_eq!(TaskV4::parse("   ").unwrap_err(), ParseNetError::EmptyTask);
+        assert_eq!(TaskV6::parse("   ").unwrap_err(), ParseNetError::EmptyTask);
+        assert_eq!(
+            TaskV4::parse("").unwrap_err().to_string(),
+            r#"Input is empty, expected NET/CIDR -> N, e.g. "10.0.0.0/24 -> 4""#
+        );
+        for text in ["garbage", "10.0.0.0/24 -> 4x", "10.0.0.0/24 ->"] {
+            assert_eq!(
+                TaskV4::parse(text).unwrap_err(),
+                ParseNetError::MalformedTask(text.to_owned())
+            );
+        }
+        assert_eq!(
+            TaskV4::parse("garbage").unwrap_err().to_string(),
+            r#"Could not parse "garbage", expected NET/CIDR -> N, e.g. "10.0.0.0/24 -> 4""#
+        );
+    }
+
+    #[test]
+    fn parse_rejects_too_many_subnets() {
+        assert_eq!(
+            TaskV4::parse("10.0.0.0/30 -> 8").unwrap_err(),
+            ParseNetError::TooManySubnets {
+                subnets: 8,
+                target_cidr: 33,
+                max: 32
+            }
+        );
+        assert!(TaskV4::parse("10.0.0.0/30 -> 4").is_ok());
+        assert!(matches!(
+            TaskV6::parse("2001:db8::/126 -> 8"),
+            Err(ParseNetError::TooManySubnets { max: 128, .. })
+        ));
+    }
+
+    #[test]
+    fn split_0_into_one_subnet() {
+        let task = task_v4("0.0.0.0/0 -> 1");
+        assert_eq!(
+            task.summary_line(false),
+            "0.0.0.0/0 -> 1 subnets (/0, 4294967294 hosts each)"
+        );
+        assert_eq!(
+            task.target_networks()[0].network_address(),
+            Ipv4Addr::UNSPECIFIED
+        );
+
+        let task = TaskV6::parse("::/0 -> 1").unwrap();
+        let nets = task.target_networks();
+        assert_eq!(nets.len(), 1);
+        assert_eq!(nets[0].network_address(), Ipv6Addr::UNSPECIFIED);
+        assert_eq!(nets[0].cidr(), 0);
+        assert!(task
+            .to_markdown(None, MarkdownOptions::default())
+            .contains("| 1 | :: |"));
     }
 }
@@ -0,0 +1,62 @@
+use std::process::{Command, Output};
+
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_ipaufgabengenerator"))
+        .args(args)
+        .output()
+        .expect("could not run ipaufgabengenerator")
+}
+
+#[test]
+fn solve_addresses_only() {
+    let output = run(&["v4", "solve", "10.0.0.0/24 -> 4", "--addresses-only"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "10.0.0.0/26\n10.0.0.64/26\n10.0.0.128/26\n10.0.0.192/26\n"
+    );
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+#[test]
+fn grade_reports_wrong_broadcast() {
+    let output = run(&[
+        "v4",
+        "grade",
+        &fixture("grade_tasks.txt"),
+        &fixture("grade_answers.csv"),
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Aufgabe 1: bestanden\n\
+         Aufgabe 2: nicht bestanden\n  \
+         Netz 2 Broadcastadresse: erwartet 192.168.0.255, angegeben 192.168.0.254\n"
+    );
+}
+
+#[test]
+fn grade_missing_file() {
+    let output = run(&[
+        "v4",
+        "grade",
+        &fixture("missing.txt"),
+        &fixture("grade_answers.csv"),
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Could not read"));
+    assert!(stderr.contains("grade <TASKS> <ANSWERS>"));
+}
+
+#[test]
+fn gen_combined_write_error() {
+    let output = run(&["v4", "gen", "1", "--combined", &fixture("missing/out.md")]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Could not write"));
+    assert!(!stderr.contains("panicked"));
+}